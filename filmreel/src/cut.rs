@@ -217,57 +217,90 @@ impl Register {
     }
 
     /// Takes a [`crate::Frame`] string value and compares it against a payload string value
-    /// returning any declared cut variables found
+    /// returning every declared cut variable found along with its payload value:
+    /// `"order ${ORDER_ID} for ${CUSTOMER}"` and `"order 7 for ben"` return
+    /// `{"ORDER_ID": "7", "CUSTOMER": "ben"}`
+    ///
+    /// Returns an [`Err`] if the payload string does not follow the frame string or if the cut
+    /// variables can be extracted in more than one way, such as `"${FIRST}${LAST}"`
     pub fn write_match(
-        var_name: &str,
         frame_str: &str,
         payload_str: &str,
-    ) -> Result<Option<String>, FrError> {
-        let re = Regex::new(&format!(
-            r"(?x)
-                (?P<head_val>.*)   # value preceding cut var
-                (?P<esc_char>\\)?  # escape character
-                (?P<cut_decl>\$\{{
-                {}
-                \}})               # Cut Variable Declaration
-                (?P<tail_val>.*)   # value following cut var
-                ",
-            var_name
-        ))
-        .expect("write-match regex error");
-
-        let mut matches: Vec<&str> = Vec::new();
-        for mat in re.captures_iter(frame_str) {
-            // continue if the leading brace is escaped but strip "\\" from the match
+    ) -> Result<HashMap<String, String>, FrError> {
+        lazy_static! {
+            static ref WRITE_MATCH: Regex = Regex::new(
+                r"(?x)
+                (?P<esc_char>\\)?              # escape character
+                \$\{(?P<cut_var>[A-Za-z_0-9]+)} # Cut Variable Declaration
+                "
+            )
+            .unwrap();
+        }
+
+        // split the frame string into the literals surrounding each cut variable
+        let mut names: Vec<&str> = Vec::new();
+        let mut literals: Vec<String> = vec![String::new()];
+        let mut last = 0;
+        for mat in WRITE_MATCH.captures_iter(frame_str) {
+            let full_match = mat.get(0).expect("capture missing");
+            let literal = literals.last_mut().expect("missing literal");
+            literal.push_str(&frame_str[last..full_match.start()]);
+            last = full_match.end();
+            // an escaped declaration is a literal once "\\" is stripped from the match
             if mat.name("esc_char").is_some() {
+                literal.push_str(&full_match.as_str()[1..]);
                 continue;
             }
-
-            let head_val = mat.name("head_val").expect("head_val error").as_str();
-            let tail_val = mat.name("tail_val").expect("tail_val error").as_str();
-            if !(payload_str.starts_with(head_val) && payload_str.ends_with(tail_val)) {
+            names.push(mat.name("cut_var").expect("cut_var error").as_str());
+            literals.push(String::new());
+        }
+        if names.is_empty() {
+            return Ok(HashMap::new());
+        }
+        literals
+            .last_mut()
+            .expect("missing literal")
+            .push_str(&frame_str[last..]);
+
+        // lazy captures return the shortest leading values and greedy captures the longest ones,
+        // the cut variables can only be extracted in a single way when both agree
+        let pattern = |capture: &str| {
+            let escaped: Vec<String> = literals.iter().map(|l| regex::escape(l)).collect();
+            let re = format!("(?s)^{}$", escaped.join(capture));
+            Regex::new(&re).expect("write-match regex error")
+        };
+        let (lazy, greedy) = match (
+            pattern("(.*?)").captures(payload_str),
+            pattern("(.*)").captures(payload_str),
+        ) {
+            (Some(lazy), Some(greedy)) => (lazy, greedy),
+            _ => {
                 return Err(FrError::WriteInstruction(
                     "Frame String templating mismatch",
-                ));
+                ))
             }
+        };
 
-            matches.push(
-                payload_str
-                    .trim_start_matches(head_val)
-                    .trim_end_matches(tail_val),
-            );
+        let mut matches: HashMap<String, String> = HashMap::new();
+        for (i, name) in names.into_iter().enumerate() {
+            let value = &lazy[i + 1];
+            if value != &greedy[i + 1] {
+                return Err(FrError::WriteInstruction(
+                    "Frame String templating ambiguity",
+                ));
+            }
+            // a cut variable declared more than once must hold the same value throughout
+            match matches.get(name) {
+                Some(prev) if prev != value => {
+                    return Err(FrError::WriteInstruction(
+                        "Frame String templating mismatch",
+                    ));
+                }
+                _ => matches.insert(name.to_string(), value.to_string()),
+            };
         }
 
-        // `_ =>` is not possible for now, but guard with panic
-        match matches.len() {
-            0 => Ok(None),
-            1 => Ok(Some(
-                matches.pop().expect("missing match value").to_string(),
-            )),
-            _ => unreachable!(
-                "Multiple variable matches in string not permitted for write instruction"
-            ),
-        }
+        Ok(matches)
     }
 
     /// Inserts a Value entry into the Register's Cut Variables
@@ -559,30 +592,57 @@ mod tests {
     }
 
     #[rstest(
-        var,
         frame,
         payload,
         expected,
-        case("NAME", "My name is ${NAME}.", "My name is Slim Shady.", "Slim Shady"),
-        case("SINGLE", "${SINGLE}", "my big hit", "my big hit"),
+        case("My name is ${NAME}.", "My name is Slim Shady.", Ok(vec![("NAME", "Slim Shady")])),
+        case("${SINGLE}", "my big hit", Ok(vec![("SINGLE", "my big hit")])),
+        case("${SINGLE}|${SINGLE}", "1|1", Ok(vec![("SINGLE", "1")])),
+        case(
+            "order ${ORDER_ID} for ${CUSTOMER}",
+            "order 7 for Slim Shady",
+            Ok(vec![("ORDER_ID", "7"), ("CUSTOMER", "Slim Shady")])
+        ),
+        case(
+            "/users/${USER_ID}/posts/${POST_ID}",
+            "/users/1/posts/22",
+            Ok(vec![("USER_ID", "1"), ("POST_ID", "22")])
+        ),
+        case(r"\${NAME} is ${NAME}", "${NAME} is Slim", Ok(vec![("NAME", "Slim")])),
+        case("no variables", "no match", Ok(vec![])),
         case(
-            "SINGLE",
             "${SINGLE}|${SINGLE}",
             "1|2",
-            "WriteInstructionError: Frame String templating mismatch"
+            Err("WriteInstructionError: Frame String templating mismatch")
         ),
         case(
-            "SINGLE",
             "${SINGLE}|",
             "|2",
-            "WriteInstructionError: Frame String templating mismatch"
+            Err("WriteInstructionError: Frame String templating mismatch")
+        ),
+        case(
+            "${FIRST}${LAST}",
+            "SlimShady",
+            Err("WriteInstructionError: Frame String templating ambiguity")
+        ),
+        case(
+            "${A} for ${B}",
+            "1 for 2 for 3",
+            Err("WriteInstructionError: Frame String templating ambiguity")
         )
     )]
-    fn test_write_match(var: &str, frame: &str, payload: &str, expected: &str) {
-        match Register::write_match(var, frame, &payload.to_string()) {
-            Ok(mat) => assert_eq!(expected, mat.unwrap()),
-            Err(err) => assert_eq!(expected, err.to_string()),
-        }
+    fn test_write_match(frame: &str, payload: &str, expected: Result<Vec<(&str, &str)>, &str>) {
+        let expected = expected
+            .map(|vars| {
+                vars.into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<String, String>>()
+            })
+            .map_err(str::to_string);
+        assert_eq!(
+            expected,
+            Register::write_match(frame, payload).map_err(|e| e.to_string())
+        );
     }
 
     #[test]
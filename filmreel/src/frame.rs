@@ -1,6 +1,6 @@
 use crate::{
     cut::Register,
-    error::FrError,
+    error::{FrError, WithPath},
     response::Response,
     utils::{ordered_set, ordered_str_map},
 };
@@ -10,7 +10,7 @@ use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     convert::TryFrom,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 /// Represents the entire deserialized frame file.
@@ -61,6 +61,41 @@ impl<'a> Frame<'a> {
         to_value(&self.response.body)
     }
 
+    /// Reads the file named by the request's `"body_file"` key into the request body so that it
+    /// is hydrated like an inline body. The filepath is taken relative to `dir`, usually the
+    /// directory of the frame file. A file holding JSON becomes the request body as is while any
+    /// other text is stored as a string and flagged to be sent as a `"raw"` body.
+    pub fn load_body_file<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), FrError> {
+        let etc = match &mut self.request.etc {
+            Some(Value::Object(etc)) => etc,
+            _ => return Ok(()),
+        };
+        let body_file = match etc.get("body_file") {
+            Some(Value::String(f)) => dir.as_ref().join(f),
+            Some(_) => {
+                return Err(FrError::FrameParse(
+                    "request[\"body_file\"] must be a filepath string",
+                ))
+            }
+            None => return Ok(()),
+        };
+        if self.request.body.is_some() {
+            return Err(FrError::FrameParse(
+                "request[\"body_file\"] cannot be declared alongside a request body",
+            ));
+        }
+
+        let contents = crate::file_to_string(&body_file).with_path(&body_file)?;
+        self.request.body = match serde_json::from_str(&contents) {
+            Ok(json) => Some(json),
+            Err(_) => {
+                etc.entry("raw").or_insert(Value::Bool(true));
+                Some(Value::String(contents))
+            }
+        };
+        Ok(())
+    }
+
     /// Traverses Frame properties where Read Operations are permitted and
     /// performs Register.read_operation on Strings with Cut Variables
     pub fn hydrate(&mut self, reg: &Register, hide: bool) -> Result<(), FrError> {
@@ -71,6 +106,15 @@ impl<'a> Frame<'a> {
         if let Some(response_body) = &mut self.response.body {
             Self::hydrate_val(&set, response_body, reg, hide)?;
         }
+        if let Some(response_header) = &mut self.response.header {
+            Self::hydrate_val(&set, response_header, reg, hide)?;
+        }
+        if let Some(response_errors) = &mut self.response.errors {
+            Self::hydrate_val(&set, response_errors, reg, hide)?;
+        }
+        if let Some(response_etc) = &mut self.response.etc {
+            Self::hydrate_val(&set, response_etc, reg, hide)?;
+        }
         if let Some(header) = &mut self.request.header {
             Self::hydrate_val(&set, header, reg, hide)?;
         }
@@ -169,6 +213,16 @@ pub enum Protocol {
     GRPC,
     #[allow(clippy::upper_case_acronyms)]
     HTTP,
+    GraphQL,
+    WebSocket,
+    #[allow(clippy::upper_case_acronyms)]
+    SSE,
+    Exec,
+    #[allow(clippy::upper_case_acronyms)]
+    SQL,
+    /// any other protocol name, sent through a transport registered by the runner
+    #[serde(untagged)]
+    Other(String),
 }
 
 /// Contains read and write instructions for the [`crate::Register`],
@@ -421,6 +475,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_load_body_file() {
+        let dir = std::env::temp_dir().join("filmreel_test_load_body_file");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("order.json"), r#"{"id": "${ORDER_ID}"}"#).unwrap();
+        std::fs::write(dir.join("order.xml"), "<id>${ORDER_ID}</id>").unwrap();
+
+        let reg = register!({ "ORDER_ID"=> "1337" });
+        let frame_json = |file: &str| {
+            format!(
+                r#"{{
+  "protocol": "HTTP",
+  "cut": {{ "from": ["ORDER_ID"] }},
+  "request": {{ "body_file": "{}", "uri": "POST /order" }},
+  "response": {{ "status": 200 }}
+}}"#,
+                file
+            )
+        };
+
+        let json_frame = frame_json("order.json");
+        let mut frame = Frame::new(&json_frame).unwrap();
+        frame.load_body_file(&dir).unwrap();
+        frame.hydrate(&reg, false).unwrap();
+        assert_eq!(Some(json!({"id": "1337"})), frame.request.body);
+        assert_eq!(Some(json!({"body_file": "order.json"})), frame.request.etc);
+
+        let xml_frame = frame_json("order.xml");
+        let mut frame = Frame::new(&xml_frame).unwrap();
+        frame.load_body_file(&dir).unwrap();
+        frame.hydrate(&reg, false).unwrap();
+        assert_eq!(Some(json!("<id>1337</id>")), frame.request.body);
+        assert_eq!(
+            Some(json!({"body_file": "order.xml", "raw": true})),
+            frame.request.etc
+        );
+
+        let missing_frame = frame_json("missing.json");
+        let mut frame = Frame::new(&missing_frame).unwrap();
+        assert!(frame.load_body_file(&dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_instruction_set_validate() {
         let set = InstructionSet {
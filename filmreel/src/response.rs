@@ -26,11 +26,19 @@ const MISSING_SELECTION_ERR: &str = "selection missing from Frame body";
 pub struct Response<'a> {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub body:       Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header:     Option<Value>,
+    /// errors returned alongside the body by protocols such as GraphQL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors:     Option<Value>,
     //
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub etc:        Option<Value>, // https://github.com/serde-rs/serde/issues/1626
     #[serde(skip_serializing)]
     pub validation: Option<Validation<'a>>,
+    /// latency budget of the request in milliseconds
+    #[serde(default, skip_serializing)]
+    pub max_ms:     Option<u64>,
     pub status:     u32,
 }
 
@@ -45,11 +53,12 @@ impl<'a> Response<'a> {
         if self.validation.is_none() {
             return Ok(());
         }
-        // for now hardcode checking only response body
+        // for now hardcode checking only the response body, header, errors and flattened fields
         for k in self.validation.as_ref().unwrap().keys() {
-            if !k.trim_start_matches('.').starts_with("'response'.'body'") {
+            if query_target(k, self.etc.as_ref()).is_none() {
                 return Err(FrError::ReadInstruction(
-                    "validation options currently only support the response body",
+                    "validation options currently only support the response body, header, errors \
+                     and flattened response fields",
                 ));
             }
         }
@@ -80,8 +89,9 @@ impl<'a> Response<'a> {
             let payload_val = select_value(&payload_response, query)?;
 
             if let Value::String(payload_str) = &payload_val {
-                let write_match = Register::write_match(k, &frame_str, payload_str)?;
-                if let Some(mat) = write_match {
+                // several cut variables can be written from a single string
+                let mut write_match = Register::write_match(&frame_str, payload_str)?;
+                if let Some(mat) = write_match.remove(k.as_ref()) {
                     write_matches.insert(k, to_value(mat)?);
                 }
                 continue;
@@ -100,7 +110,9 @@ impl<'a> Response<'a> {
 
     /// Applies the validations using the BTree key as the Value selector
     pub fn apply_validation(&mut self, other: &mut Self) -> Result<(), FrError> {
-        if self.body.is_none() || other.body.is_none() || self.validation.is_none() {
+        self.retain_declared_header(other);
+        self.retain_declared_etc(other);
+        if self.validation.is_none() {
             return Ok(());
         }
         for (k, v) in self.validation.as_ref().unwrap().iter() {
@@ -109,22 +121,27 @@ impl<'a> Response<'a> {
                 continue;
             }
 
-            let selector = new_mut_selector(strip_query(k))?;
+            let target = query_target(k, self.etc.as_ref()).ok_or(FrError::ReadInstruction(
+                "validation options currently only support the response body, header, errors \
+                 and flattened response fields",
+            ))?;
+            let (self_val, other_val) = match target {
+                BODY_TARGET => (self.body.as_mut(), other.body.as_mut()),
+                HEADER_TARGET => (self.header.as_mut(), other.header.as_mut()),
+                ERRORS_TARGET => (self.errors.as_mut(), other.errors.as_mut()),
+                _ => (self.etc.as_mut(), other.etc.as_mut()),
+            };
+            let (self_val, other_val) = match (self_val, other_val) {
+                (Some(s), Some(o)) => (s, o),
+                _ => continue,
+            };
+
+            let selector = new_mut_selector(strip_query(k, target))?;
             if v.unordered {
-                v.apply_unordered(
-                    k,
-                    &selector,
-                    self.body.as_mut().unwrap(),
-                    other.body.as_mut().unwrap(),
-                )?;
+                v.apply_unordered(k, &selector, self_val, other_val)?;
             }
             if v.partial {
-                v.apply_partial(
-                    k,
-                    &selector,
-                    self.body.as_mut().unwrap(),
-                    other.body.as_mut().unwrap(),
-                )?;
+                v.apply_partial(k, &selector, self_val, other_val)?;
             }
         }
 
@@ -133,42 +150,132 @@ impl<'a> Response<'a> {
 
         Ok(())
     }
+
+    /// A server will return far more headers than a frame would care to declare, so only the
+    /// payload headers named in the frame response are kept for comparison. Header names are
+    /// matched case insensitively, keeping the casing used in the frame.
+    fn retain_declared_header(&self, other: &mut Self) {
+        let declared = match &self.header {
+            Some(Value::Object(map)) => map,
+            Some(_) => return,
+            None => {
+                other.header = None;
+                return;
+            }
+        };
+
+        if let Some(Value::Object(payload)) = &other.header {
+            let retained: Map<String, Value> = declared
+                .keys()
+                .filter_map(|k| {
+                    payload
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(k))
+                        .map(|(_, v)| (k.clone(), v.clone()))
+                })
+                .collect();
+            other.header = Some(Value::Object(retained));
+        }
+    }
+
+    /// Protocols attach metadata such as cookies, trailers or errors to the flattened response
+    /// fields, only the fields declared in the frame response are kept for comparison. The keys of
+    /// a declared object are retained the same way, one level deep, so that
+    /// `"cookie": {"session": "${SESSION}"}` ignores other cookies and `"error": {"code": 3}`
+    /// ignores the message of the error while the declared keys are still compared in full.
+    fn retain_declared_etc(&self, other: &mut Self) {
+        let declared = match &self.etc {
+            Some(Value::Object(map)) => map,
+            _ => return,
+        };
+        let payload = match &mut other.etc {
+            Some(Value::Object(map)) => map,
+            _ => return,
+        };
+
+        payload.retain(|k, _| declared.contains_key(k));
+        for (k, v) in payload.iter_mut() {
+            if let (Some(Value::Object(declared_obj)), Value::Object(payload_obj)) =
+                (declared.get(k), v)
+            {
+                payload_obj.retain(|k, _| declared_obj.contains_key(k));
+            }
+        }
+    }
+}
+
+const BODY_TARGET: &str = "'response'.'body'";
+const HEADER_TARGET: &str = "'response'.'header'";
+const ERRORS_TARGET: &str = "'response'.'errors'";
+// fields flattened into the response by a protocol such as `"'response'.'error'"`
+const ETC_TARGET: &str = "'response'";
+// query_target returns the portion of the response a validation selector points to, selectors
+// of flattened fields must name one of the fields declared by the frame response
+fn query_target(query: &str, etc: Option<&Value>) -> Option<&'static str> {
+    let query = query.trim_start_matches('.');
+    let selects = |target: &str| {
+        query
+            .strip_prefix(target)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    };
+    if let Some(target) = [BODY_TARGET, HEADER_TARGET, ERRORS_TARGET]
+        .iter()
+        .find(|t| selects(t))
+    {
+        return Some(target);
+    }
+    let declared = match etc {
+        Some(Value::Object(map)) => map,
+        _ => return None,
+    };
+    declared
+        .keys()
+        .any(|f| selects(&format!("{}.'{}'", ETC_TARGET, f)))
+        .then_some(ETC_TARGET)
 }
 
-// For now selector queries are only used on the reponse body
-// selector logic takes the body Value object while mainting a valid
+// For now selector queries are only used on the reponse body, header and flattened fields
+// selector logic takes the target Value object while mainting a valid
 // "whole file" query for reference's sake
 // `"'response'.'body'" => "."`
 // `"'response'.'body'.'key'" => ".'key'"`
-fn strip_query(query: &str) -> &str {
-    let body_query = query
-        .trim_start_matches('.')
-        .trim_start_matches("'response'.'body'");
+// `"'response'.'header'.'key'" => ".'key'"`
+// `"'response'.'errors'" => "."`
+// `"'response'.'error'.'details'" => ".'error'.'details'"`
+fn strip_query<'q>(query: &'q str, target: &str) -> &'q str {
+    let target_query = query.trim_start_matches('.').trim_start_matches(target);
 
-    if body_query.is_empty() {
+    if target_query.is_empty() {
         return ".";
     }
-    body_query
+    target_query
 }
 
 impl Default for Response<'_> {
     fn default() -> Self {
         Self {
             body:       None,
+            header:     None,
+            errors:     None,
             etc:        Some(json!({})),
             validation: None,
+            max_ms:     None,
             status:     0,
         }
     }
 }
 
-/// PartialEq needs to exlcude [`Response.validation`] to ensure that [`Response::apply_validation`] can
+/// PartialEq needs to exlcude [`Response.validation`] and [`Response.max_ms`] to ensure that [`Response::apply_validation`] can
 /// diffentiatiate between the parent `Response` (the one pulled directle from the filmReel file)
 /// and the child [`Response`] (one deserialized from returned data) since the client validations
 /// should always be[`Option::None`]
 impl<'a> PartialEq for Response<'a> {
     fn eq(&self, other: &Self) -> bool {
-        self.body.eq(&other.body) && self.etc.eq(&other.etc) && self.status.eq(&other.status)
+        self.body.eq(&other.body)
+            && self.header.eq(&other.header)
+            && self.errors.eq(&other.errors)
+            && self.etc.eq(&other.etc)
+            && self.status.eq(&other.status)
     }
 }
 
@@ -290,54 +397,71 @@ impl Validator {
                         Err(e) => Err(e),
                     })?;
 
-                // elements removed form OtherIdxMap are put in
-                // placeholder_indices so that Other can be later drained
-                // of the appropriate Value::Null elements using `other_selection.retain(...);`
+                // indices of Other that have been paired are put in placeholder_indices so that
+                // Other can be later drained of the Value::Null elements swapped into their place
                 let mut placeholder_indices: HashSet<usize> = HashSet::new();
-                // sink collects successful matches of elements from Other
-                // in the sequence (aka ordered but non-consecutive) they are present in Self
-                // sink is then prepended to other_selection
-                // thus all successful matches (those that are found in both Self and Other)
-                // will found at the front of other_selection in sequence
+                // sink collects the paired elements of Other keyed by the index of their pair in
+                // Self, sink is then prepended to other_selection so that every paired element is
+                // found at the front of other_selection in the order of Self
                 let mut sink: BTreeMap<usize, Value> = BTreeMap::new();
                 /*
-                remove from other_selection starting with the last index of other
-                and insert into the index of where it is found in self
+                elements are paired in two passes: first with an identical element of Other, then
+                the elements of Self left unpaired with an element of Other sharing the same
+                object keys, so that a mismatch is reported against its closest counterpart
                 ----------------
-                Self:  [A, B, C, C]
-                Other: [B, A, C, C]
-                Sink:  []
-                OtherIdxMap/IdxMap: {B: [0], A: [1]:, C: [2, 3]}
+                Self:  [{"id":1}, {"id":2}]
+                Other: [{"id":3}, {"id":1}]
+                ExactIdxMap: {{"id":3}:[0], {"id":1}:[1]}
+                OtherIdxMap: {{"id":null}:[0,1]}
 
                 Expected iterations:
-                                                                    Sink[       ];Other[B,   A,   C,   C   ];IdxMap{B:[0],A:[1]C:[2,3]}
-                i=0 v=A:
-                OtherIdxMap[A].remove(0)->1;Null->Other[1]->Sink[0];Sink[A      ];Other[B,   Null,C,   C   ];IdxMap{B:[0],C:[2,3]     }
-                i=1 v=B:
-                OtherIdxMap[B].remove(0)->0;Null->Other[0]->Sink[1];Sink[A,B    ];Other[Null,Null,C,   C   ];IdxMap{C:[2,3]           }
-                i=2 v=C:
-                OtherIdxMap[C].remove(0)->2;Null->Other[2]->Sink[2];Sink[A,B,C  ];Other[Null,Null,Null,C   ];IdxMap{C:[3]             }
-                i=3 v=C:
-                OtherIdxMap[C].remove(0)->3;Null->Other[3]->Sink[3];Sink[A,B,C,C];Other[Null,Null,Null,Null];IdxMap{                  }
+                exact pass:
+                i=0 v={"id":1}: ExactIdxMap[{"id":1}].remove(0)->1;FromIndices{0:1    }
+                i=1 v={"id":2}: no identical element in Other
+                key pass:
+                i=1 v={"id":2}: OtherIdxMap[{"id":null}] retains [0];  FromIndices{0:1,1:0}
+                swap:
+                Sink[{"id":1},{"id":3}];Other[Null,Null] -> Other[{"id":1},{"id":3}]
                 ----------------
+                the single mismatch left is then reported as {"id":2} against {"id":3}
                 */
+                let mut exact_idx_map: HashMap<Key<Hash>, Vec<usize>> = HashMap::new();
+                for (i, v) in other_selection.iter().enumerate() {
+                    exact_idx_map.entry(to_key(v)?).or_default().push(i);
+                }
+                let mut from_indices: BTreeMap<usize, usize> = BTreeMap::new();
                 for (to_idx, v) in self_selection.iter().enumerate() {
-                    let v_hash = hash_value(v)?;
-
-                    if let Some(other_indices) = other_idx_map.get_mut(&v_hash) {
-                        // remove idx from Vec so that it is not reused
-                        let from_idx = other_indices.remove(0);
-                        // retain reference of key to be removed so we can swap it
-                        // with a Null when doing substitution
-                        let mut to_value = Value::Null;
-                        // swap places with the Null in the match_sink so that values in
-                        // Other maintain a valid index reference
-                        std::mem::swap(&mut to_value, &mut other_selection[from_idx]);
-                        sink.insert(to_idx, to_value);
-                        // make sure to remove the Null after iteration
-                        placeholder_indices.insert(from_idx);
+                    if let Some(other_indices) = exact_idx_map.get_mut(&to_key(v)?) {
+                        if !other_indices.is_empty() {
+                            let from_idx = other_indices.remove(0);
+                            from_indices.insert(to_idx, from_idx);
+                            placeholder_indices.insert(from_idx);
+                        }
                     }
                 }
+                // key pass
+                for (to_idx, v) in self_selection.iter().enumerate() {
+                    if from_indices.contains_key(&to_idx) {
+                        continue;
+                    }
+                    if let Some(other_indices) = other_idx_map.get_mut(&hash_value(v)?) {
+                        // remove indices already matched so that they are not reused
+                        other_indices.retain(|i| !placeholder_indices.contains(i));
+                        if !other_indices.is_empty() {
+                            let from_idx = other_indices.remove(0);
+                            from_indices.insert(to_idx, from_idx);
+                            // make sure to remove the Null after iteration
+                            placeholder_indices.insert(from_idx);
+                        }
+                    }
+                }
+                for (to_idx, from_idx) in from_indices {
+                    // swap places with a Null so that values in Other maintain a valid index
+                    // reference
+                    let mut to_value = Value::Null;
+                    std::mem::swap(&mut to_value, &mut other_selection[from_idx]);
+                    sink.insert(to_idx, to_value);
+                }
 
                 // we've found no intersections; return early
                 if sink.is_empty() {
@@ -429,6 +553,286 @@ mod tests {
         assert_eq!(expected_match, mat.unwrap());
     }
 
+    #[test]
+    fn test_match_payload_response_header() {
+        let set = InstructionSet {
+            reads:          from![],
+            writes:         to!({"LOCATION"=> "'response'.'header'.'Location'"}),
+            hydrate_writes: true,
+        };
+        let mut frame_response = Response {
+            header: Some(json!({"Location": "/users/${LOCATION}"})),
+            status: 201,
+            ..Default::default()
+        };
+        let mut payload_response = Response {
+            header: Some(json!({
+                "content-type": "application/json",
+                "location": "/users/42"
+            })),
+            status: 201,
+            ..Default::default()
+        };
+        frame_response
+            .apply_validation(&mut payload_response)
+            .unwrap();
+        assert_eq!(
+            Some(json!({"Location": "/users/42"})),
+            payload_response.header
+        );
+
+        let mat = frame_response
+            .match_payload_response(&set, &payload_response)
+            .unwrap();
+        let mut expected_match = HashMap::new();
+        expected_match.insert("LOCATION", to_value("42").unwrap());
+        assert_eq!(expected_match, mat.unwrap());
+    }
+
+    #[test]
+    fn test_match_payload_response_multiple_vars() {
+        let set = InstructionSet {
+            reads:          from![],
+            writes:         to!({
+                "USER_ID"=> "'response'.'header'.'Location'",
+                "POST_ID"=> "'response'.'header'.'Location'"
+            }),
+            hydrate_writes: true,
+        };
+        let frame_response = Response {
+            header: Some(json!({"Location": "/users/${USER_ID}/posts/${POST_ID}"})),
+            status: 201,
+            ..Default::default()
+        };
+        let payload_response = Response {
+            header: Some(json!({"Location": "/users/42/posts/7"})),
+            status: 201,
+            ..Default::default()
+        };
+
+        let mat = frame_response
+            .match_payload_response(&set, &payload_response)
+            .unwrap();
+        let mut expected_match = HashMap::new();
+        expected_match.insert("USER_ID", to_value("42").unwrap());
+        expected_match.insert("POST_ID", to_value("7").unwrap());
+        assert_eq!(expected_match, mat.unwrap());
+    }
+
+    #[rstest(
+        frame_header,
+        payload_header,
+        expected,
+        case(None, Some(json!({"etag": "1"})), None),
+        case(
+            Some(json!({"ETag": "1"})),
+            Some(json!({"etag": "1", "server": "nginx"})),
+            Some(json!({"ETag": "1"}))
+        ),
+        case(
+            Some(json!({"ETag": "1"})),
+            Some(json!({"server": "nginx"})),
+            Some(json!({}))
+        )
+    )]
+    fn test_retain_declared_header(
+        frame_header: Option<Value>,
+        payload_header: Option<Value>,
+        expected: Option<Value>,
+    ) {
+        let frame_response = Response {
+            header: frame_header,
+            ..Default::default()
+        };
+        let mut payload_response = Response {
+            header: payload_header,
+            ..Default::default()
+        };
+        frame_response.retain_declared_header(&mut payload_response);
+        assert_eq!(expected, payload_response.header);
+    }
+
+    #[rstest(
+        frame_etc,
+        payload_etc,
+        expected,
+        case(json!({}), json!({"cookie": {"session": "1"}}), json!({})),
+        case(
+            json!({"cookie": {"session": "1"}}),
+            json!({"cookie": {"session": "1", "theme": "dark"}, "redirects": []}),
+            json!({"cookie": {"session": "1"}})
+        ),
+        case(
+            json!({"cookie": {"session": "1"}}),
+            json!({"redirects": []}),
+            json!({})
+        ),
+        case(
+            json!({"user_level": "admin"}),
+            json!({"user_level": ["admin"]}),
+            json!({"user_level": ["admin"]})
+        )
+    )]
+    fn test_retain_declared_etc(frame_etc: Value, payload_etc: Value, expected: Value) {
+        let frame_response = Response {
+            etc: Some(frame_etc),
+            ..Default::default()
+        };
+        let mut payload_response = Response {
+            etc: Some(payload_etc),
+            ..Default::default()
+        };
+        frame_response.retain_declared_etc(&mut payload_response);
+        assert_eq!(Some(expected), payload_response.etc);
+    }
+
+    #[test]
+    fn test_retain_declared_etc_mismatch() {
+        let frame: Response = serde_json::from_value(json!({
+            "error": {"code": 3, "details": [{"field": "name"}]},
+            "status": 3
+        }))
+        .unwrap();
+        let payload = |code: u32, field: &str| -> Response {
+            serde_json::from_value(json!({
+                "error": {"code": code, "message": "invalid", "details": [{"field": field}]},
+                "trailers": {"x-request-id": "1"},
+                "status": 3
+            }))
+            .unwrap()
+        };
+
+        // undeclared fields and keys are left out of the comparison
+        let mut matching = payload(3, "name");
+        frame.clone().apply_validation(&mut matching).unwrap();
+        assert_eq!(frame, matching);
+
+        // declared keys are still compared in full however deep the mismatch
+        for mut mismatch in [payload(5, "name"), payload(3, "age")] {
+            frame.clone().apply_validation(&mut mismatch).unwrap();
+            assert_ne!(frame, mismatch);
+        }
+    }
+
+    #[rstest(
+        selector,
+        valid,
+        case("'response'.'body'", true),
+        case("'response'.'body'.[0].'id'", true),
+        case(".'response'.'header'.'etag'", true),
+        case("'response'.'errors'", true),
+        case("'response'.'error'.'details'", true),
+        case("'response'.'trailers'", true),
+        case("'response'.'offset'", true),
+        case("'response'.'bodyy'", false),
+        case("'response'.'bogus'", false),
+        case("'response'.'errorr'", false),
+        case("'response'.'cookie'", false),
+        case("'response'", false),
+        case("'request'.'body'", false)
+    )]
+    fn test_validate(selector: &str, valid: bool) {
+        // flattened fields can only be selected when the frame response declares them, such as
+        // the "offset" returned by a plugin
+        let frame: Response = serde_json::from_value(json!({
+            "validation": {selector: {"partial": true}},
+            "error": {"code": 3, "details": []},
+            "trailers": {},
+            "offset": 42,
+            "status": 0
+        }))
+        .unwrap();
+        assert_eq!(valid, frame.validate().is_ok());
+    }
+
+    #[test]
+    fn test_etc_validation() {
+        let mut frame: Response = serde_json::from_value(json!({
+            "validation": {
+                "'response'.'error'.'details'": {"partial": true, "unordered": true}
+            },
+            "body": "name is required",
+            "error": {
+                "code": 3,
+                "message": "name is required",
+                "details": [{"@type": "type.googleapis.com/google.rpc.BadRequest"}]
+            },
+            "status": 3
+        }))
+        .unwrap();
+        let mut payload: Response = serde_json::from_value(json!({
+            "body": "name is required",
+            "error": {
+                "code": 3,
+                "message": "name is required",
+                "details": [
+                    {"@type": "type.googleapis.com/google.rpc.RetryInfo", "retryDelay": "1s"},
+                    {"@type": "type.googleapis.com/google.rpc.BadRequest"}
+                ]
+            },
+            "trailers": {},
+            "status": 3
+        }))
+        .unwrap();
+        assert!(frame.validate().is_ok());
+
+        frame.apply_validation(&mut payload).unwrap();
+        assert_eq!(frame, payload);
+    }
+
+    #[test]
+    fn test_errors_validation() {
+        let mut frame: Response = serde_json::from_value(json!({
+            "validation": {
+                "'response'.'errors'": {"partial": true, "unordered": true}
+            },
+            "body": {"user": null},
+            "errors": [{"message": "user not found", "path": ["user"]}],
+            "status": 200
+        }))
+        .unwrap();
+        let mut payload: Response = serde_json::from_value(json!({
+            "body": {"user": null},
+            "errors": [
+                {"message": "deprecated field", "path": ["user", "nickname"]},
+                {"message": "user not found", "path": ["user"]}
+            ],
+            "status": 200
+        }))
+        .unwrap();
+        assert!(frame.validate().is_ok());
+
+        frame.apply_validation(&mut payload).unwrap();
+        assert_eq!(frame, payload);
+
+        // errors are always compared, even when the frame does not declare any
+        let frame: Response =
+            serde_json::from_value(json!({"body": {"user": null}, "status": 200})).unwrap();
+        let payload: Response = serde_json::from_value(json!({
+            "body": {"user": null},
+            "errors": [{"message": "user not found"}],
+            "status": 200
+        }))
+        .unwrap();
+        assert_ne!(frame, payload);
+    }
+
+    #[test]
+    fn test_max_ms() {
+        let frame_response: Response =
+            serde_json::from_str(r#"{"max_ms": 300, "status": 200}"#).unwrap();
+        assert_eq!(Some(300), frame_response.max_ms);
+        assert_eq!(Some(json!({})), frame_response.etc);
+        // the latency budget is never compared against a payload response
+        assert_eq!(
+            Response {
+                status: 200,
+                ..Default::default()
+            },
+            frame_response
+        );
+    }
+
     const SIMPLE_FRAME: &str = r#"{ "body": %s, "status": 200 }"#;
     const PARTIAL_FRAME: &str = r#"
 {
@@ -506,6 +910,7 @@ mod tests {
         let string_arr = r#"["A","B","C"]"#;
         let with_f32 = r#"["A","B","C",13.37]"#;
         let with_dupes = r#"["A","B","C","A","A"]"#;
+        let objects = r#"[{"id":1},{"id":2}]"#;
 
         match case {
             1 => (map_arr, r#"{"A":true,"B":true,"C":true}"#, true),
@@ -520,6 +925,9 @@ mod tests {
             10 => (string_arr, r#"["B","A","D","C"]"#, false),
             11 => (with_f32, r#"["C",13.37,"B","A"]"#, true),
             12 => (with_dupes, r#"["A","C","A","B","A"]"#, true),
+            // objects sharing the same keys are paired with their identical counterpart
+            13 => (objects, r#"[{"id":2},{"id":1}]"#, true),
+            14 => (objects, r#"[{"id":3},{"id":1}]"#, false),
             _ => panic!(),
         }
     }
@@ -537,7 +945,9 @@ mod tests {
         case(unordered_case(9)),
         case(unordered_case(10)),
         case(unordered_case(11)),
-        case(unordered_case(12))
+        case(unordered_case(12)),
+        case(unordered_case(13)),
+        case(unordered_case(14))
     )]
     fn test_unordered_validation(t_case: (&str, &str, bool)) {
         let self_response = str::replace(UNORDERED_FRAME, "%s", t_case.0);
@@ -639,6 +1049,12 @@ mod tests {
                 r#"[1,{"B":true},0]"#,
                 r#"[0,1,{"B":true}]"#,
             ),
+            15 => (
+                // messages of a gRPC stream
+                r#"[{"id":"2","name":"Jim"},{"id":"1","name":"James"}]"#,
+                r#"[{"id":"1","name":"James"},{"id":"3","name":"Jo"},{"id":"2","name":"Jim"}]"#,
+                r#"[{"id":"2","name":"Jim"},{"id":"1","name":"James"}]"#,
+            ),
             _ => panic!(),
         }
     }
@@ -658,7 +1074,8 @@ mod tests {
         case(partial_unordered_case(11)),
         case(partial_unordered_case(12)),
         case(partial_unordered_case(13)),
-        case(partial_unordered_case(14))
+        case(partial_unordered_case(14)),
+        case(partial_unordered_case(15))
     )]
     fn test_partial_unordered_validation(t_case: (&str, &str, &str)) {
         let self_response = str::replace(PARTIAL_UNORDERED, "%s", t_case.0);
@@ -42,6 +42,32 @@ test_ser_de!(protocol_grpc, Protocol::GRPC, PROTOCOL_GRPC_JSON);
 const PROTOCOL_HTTP_JSON: &str = r#""HTTP""#;
 test_ser_de!(protocol_http, Protocol::HTTP, PROTOCOL_HTTP_JSON);
 
+const PROTOCOL_GRAPHQL_JSON: &str = r#""GraphQL""#;
+test_ser_de!(protocol_graphql, Protocol::GraphQL, PROTOCOL_GRAPHQL_JSON);
+
+const PROTOCOL_WEBSOCKET_JSON: &str = r#""WebSocket""#;
+test_ser_de!(
+    protocol_websocket,
+    Protocol::WebSocket,
+    PROTOCOL_WEBSOCKET_JSON
+);
+
+const PROTOCOL_SSE_JSON: &str = r#""SSE""#;
+test_ser_de!(protocol_sse, Protocol::SSE, PROTOCOL_SSE_JSON);
+
+const PROTOCOL_EXEC_JSON: &str = r#""Exec""#;
+test_ser_de!(protocol_exec, Protocol::Exec, PROTOCOL_EXEC_JSON);
+
+const PROTOCOL_SQL_JSON: &str = r#""SQL""#;
+test_ser_de!(protocol_sql, Protocol::SQL, PROTOCOL_SQL_JSON);
+
+const PROTOCOL_OTHER_JSON: &str = r#""kafka""#;
+test_ser_de!(
+    protocol_other,
+    Protocol::Other("kafka".to_string()),
+    PROTOCOL_OTHER_JSON
+);
+
 const REQUEST_JSON: &str = r#"
 {
   "body": {
@@ -117,6 +143,50 @@ test_ser_de!(
     RESPONSE_ETC_JSON
 );
 
+const RESPONSE_HEADER_JSON: &str = r#"
+{
+  "body": "created user: ${USER_ID}",
+  "header": {
+    "Location": "/users/${USER_ID}"
+  },
+  "status": 201
+}
+    "#;
+test_ser_de!(
+    response_header,
+    Response {
+        body: Some(json!("created user: ${USER_ID}")),
+        header: Some(json!({"Location": "/users/${USER_ID}"})),
+        status: 201,
+        ..Default::default()
+    },
+    RESPONSE_HEADER_JSON
+);
+
+const RESPONSE_ERRORS_JSON: &str = r#"
+{
+  "body": {
+    "user": null
+  },
+  "errors": [
+    {
+      "message": "user ${USER_ID} not found"
+    }
+  ],
+  "status": 200
+}
+    "#;
+test_ser_de!(
+    response_errors,
+    Response {
+        body: Some(json!({"user": null})),
+        errors: Some(json!([{"message": "user ${USER_ID} not found"}])),
+        status: 200,
+        ..Default::default()
+    },
+    RESPONSE_ERRORS_JSON
+);
+
 const INSTRUCTION_SET_JSON: &str = r#"
 {
   "from": [
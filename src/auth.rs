@@ -0,0 +1,621 @@
+use anyhow::{anyhow, Context, Error};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use http::header::{self, HeaderMap, HeaderValue};
+use reqwest::{
+    blocking::{Client, Request},
+    Method,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use url::Url;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Auth is a frame level `"auth"` block that authenticates a HTTP request once it has been
+/// hydrated and built, allowing signatures to be computed over the final request
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Auth {
+    /// `{"basic": {"username": "${USER}", "password": "${PASSWORD}"}}`
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    /// `{"bearer": "${TOKEN}"}`
+    Bearer(String),
+    /// `{"hmac": {"secret": "${SECRET}", "header": "X-Signature", "canonical": "{method}\n{path}\n{body}"}}`
+    Hmac(HmacAuth),
+    /// `{"aws_sigv4": {"access_key": "${KEY}", "secret_key": "${SECRET}", "region": "us-east-1", "service": "execute-api"}}`
+    AwsSigv4(SigV4Auth),
+}
+
+/// HmacAuth signs a canonical string built from the request with HMAC-SHA256
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HmacAuth {
+    secret:           String,
+    /// header the signature is written to
+    #[serde(default = "HmacAuth::default_header")]
+    header:           String,
+    /// template of the signed string, supporting the `{method}`, `{path}`, `{query}`, `{host}`,
+    /// `{body}` and `{timestamp}` placeholders
+    #[serde(default = "HmacAuth::default_canonical")]
+    canonical:        String,
+    #[serde(default)]
+    encoding:         SignatureEncoding,
+    /// prepended to the encoded signature, such as `sha256=`
+    #[serde(default)]
+    prefix:           String,
+    /// header the unix `{timestamp}` is sent with
+    timestamp_header: Option<String>,
+}
+
+#[derive(Clone, Copy, Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// SigV4Auth signs a request using AWS Signature Version 4
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SigV4Auth {
+    access_key:    String,
+    secret_key:    String,
+    session_token: Option<String>,
+    region:        String,
+    service:       String,
+}
+
+impl Auth {
+    /// apply adds the authentication headers to a built request
+    pub fn apply(&self, request: &mut Request) -> Result<(), Error> {
+        let authorization = match self {
+            Self::Basic { username, password } => format!(
+                "Basic {}",
+                base64::encode(format!(
+                    "{}:{}",
+                    username,
+                    password.as_deref().unwrap_or_default()
+                ))
+            ),
+            Self::Bearer(token) => format!("Bearer {}", token),
+            Self::Hmac(hmac) => return hmac.apply(request, Utc::now()),
+            Self::AwsSigv4(sigv4) => return sigv4.apply(request, Utc::now()),
+        };
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&authorization)?,
+        );
+        Ok(())
+    }
+}
+
+impl HmacAuth {
+    fn default_header() -> String {
+        "X-Signature".to_string()
+    }
+
+    fn default_canonical() -> String {
+        "{body}".to_string()
+    }
+
+    fn apply(&self, request: &mut Request, now: DateTime<Utc>) -> Result<(), Error> {
+        let timestamp = now.timestamp().to_string();
+        let body = String::from_utf8_lossy(body(request)?).into_owned();
+        let url = request.url();
+        let canonical = self
+            .canonical
+            .replace("{method}", request.method().as_str())
+            .replace("{path}", url.path())
+            .replace("{query}", url.query().unwrap_or_default())
+            .replace("{host}", &host(url))
+            .replace("{timestamp}", &timestamp)
+            .replace("{body}", &body);
+
+        let signature = hmac_sha256(self.secret.as_bytes(), canonical.as_bytes());
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => hex(&signature),
+            SignatureEncoding::Base64 => base64::encode(&signature),
+        };
+
+        let headers = request.headers_mut();
+        if let Some(h) = &self.timestamp_header {
+            headers.insert(
+                header::HeaderName::from_bytes(h.as_bytes())?,
+                HeaderValue::from_str(&timestamp)?,
+            );
+        }
+        headers.insert(
+            header::HeaderName::from_bytes(self.header.as_bytes())?,
+            HeaderValue::from_str(&format!("{}{}", self.prefix, signature))?,
+        );
+        Ok(())
+    }
+}
+
+impl SigV4Auth {
+    fn apply(&self, request: &mut Request, now: DateTime<Utc>) -> Result<(), Error> {
+        let payload_hash = hex(&Sha256::digest(body(request)?));
+        let host = host(request.url());
+        let headers = request.headers_mut();
+        headers.insert(header::HOST, HeaderValue::from_str(&host)?);
+        headers.insert(
+            "x-amz-date",
+            HeaderValue::from_str(&now.format("%Y%m%dT%H%M%SZ").to_string())?,
+        );
+        if self.service == "s3" {
+            headers.insert(
+                "x-amz-content-sha256",
+                HeaderValue::from_str(&payload_hash)?,
+            );
+        }
+        if let Some(token) = &self.session_token {
+            headers.insert("x-amz-security-token", HeaderValue::from_str(token)?);
+        }
+
+        let authorization = self.authorization(
+            request.method(),
+            request.url(),
+            request.headers(),
+            &payload_hash,
+            now,
+        )?;
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&authorization)?,
+        );
+        Ok(())
+    }
+
+    /// authorization builds the SigV4 Authorization header value, signing the host, content-type
+    /// and x-amz-* headers
+    fn authorization(
+        &self,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<String, Error> {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+
+        let mut signed: Vec<(String, String)> = Vec::new();
+        for name in headers.keys().filter(|k| {
+            let k = k.as_str();
+            k == "host" || k == "content-type" || k.starts_with("x-amz-")
+        }) {
+            let values = headers
+                .get_all(name)
+                .iter()
+                .map(|v| Ok(v.to_str()?.split_whitespace().collect::<Vec<_>>().join(" ")))
+                .collect::<Result<Vec<String>, Error>>()?;
+            signed.push((name.as_str().to_string(), values.join(",")));
+        }
+        signed.sort();
+        let canonical_headers: String = signed
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v))
+            .collect();
+        let signed_headers = signed
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        // every service other than S3 expects the already encoded path to be encoded again
+        let path = match self.service.as_str() {
+            "s3" => url.path().to_string(),
+            _ => uri_encode(url.path(), false),
+        };
+        let mut query: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| (uri_encode(&k, true), uri_encode(&v, true)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, path, query, canonical_headers, signed_headers, payload_hash
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [date.as_str(), &self.region, &self.service, "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", self.secret_key).into_bytes(),
+                |key, part| hmac_sha256(&key, part.as_bytes()),
+            );
+        Ok(format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key,
+            scope,
+            signed_headers,
+            hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+        ))
+    }
+}
+
+/// OAuth2 acquires the bearer token injected into every request of a run, caching it until it
+/// expires or is rejected by a service
+#[derive(Clone, Debug)]
+pub struct OAuth2 {
+    config_path: PathBuf,
+    state:       Arc<Mutex<OAuth2State>>,
+}
+
+#[derive(Debug, Default)]
+struct OAuth2State {
+    config: Option<OAuth2Config>,
+    token:  Option<Token>,
+}
+
+/// OAuth2Config is read from the file given to `--oauth`
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OAuth2Config {
+    token_url:     String,
+    grant_type:    GrantType,
+    client_id:     String,
+    client_secret: Option<String>,
+    scope:         Option<String>,
+    audience:      Option<String>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    ClientCredentials,
+    RefreshToken,
+}
+
+#[derive(Debug)]
+struct Token {
+    access_token: String,
+    expires_at:   Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token:  String,
+    expires_in:    Option<u64>,
+    refresh_token: Option<String>,
+}
+
+impl OAuth2 {
+    /// tokens are renewed this long before they expire to avoid racing the expiry of a request
+    const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+    pub fn new(config_path: PathBuf) -> Self {
+        Self {
+            config_path,
+            state: Arc::default(),
+        }
+    }
+
+    /// bearer returns the cached access token, acquiring a new one if there is none or the cached
+    /// token has expired
+    pub fn bearer(&self, client: &Client) -> Result<String, Error> {
+        let mut state = self.state.lock().expect("oauth2 lock poisoned");
+        if let Some(token) = &state.token {
+            let expired = matches!(token.expires_at, Some(e) if e <= Instant::now());
+            if !expired {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        if state.config.is_none() {
+            let config = fs::read_to_string(&self.config_path)
+                .context(format!("oauth: {}", self.config_path.display()))?;
+            state.config = Some(serde_json::from_str(&config).context("oauth config")?);
+        }
+        let config = state.config.as_mut().expect("oauth config loaded");
+        let response = config.request_token(client)?;
+        // refresh tokens may be rotated by the authorization server
+        if let Some(refresh_token) = response.refresh_token {
+            config.refresh_token = Some(refresh_token);
+        }
+        let token = Token {
+            access_token: response.access_token,
+            expires_at:   response.expires_in.map(|s| {
+                Instant::now() + Duration::from_secs(s).saturating_sub(Self::EXPIRY_MARGIN)
+            }),
+        };
+        let access_token = token.access_token.clone();
+        state.token = Some(token);
+        Ok(access_token)
+    }
+
+    /// invalidate drops the cached token so that the next request acquires a new one
+    pub fn invalidate(&self) {
+        self.state.lock().expect("oauth2 lock poisoned").token = None;
+    }
+}
+
+/// OAuth2 providers are equal only when they share the same token cache
+impl PartialEq for OAuth2 {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl OAuth2Config {
+    fn request_token(&self, client: &Client) -> Result<TokenResponse, Error> {
+        let mut form = vec![("client_id", self.client_id.as_str())];
+        match self.grant_type {
+            GrantType::ClientCredentials => form.push(("grant_type", "client_credentials")),
+            GrantType::RefreshToken => {
+                let refresh_token = self.refresh_token.as_deref().ok_or_else(|| {
+                    anyhow!("oauth: \"refresh_token\" is required for the refresh_token grant")
+                })?;
+                form.push(("grant_type", "refresh_token"));
+                form.push(("refresh_token", refresh_token));
+            }
+        }
+        for (k, v) in [
+            ("client_secret", &self.client_secret),
+            ("scope", &self.scope),
+            ("audience", &self.audience),
+        ]
+        .iter()
+        {
+            if let Some(v) = v {
+                form.push((k, v));
+            }
+        }
+
+        let response = client
+            .post(&self.token_url)
+            .form(&form)
+            .timeout(Duration::from_secs(30))
+            .send()
+            .context(format!("oauth token request: {}", self.token_url))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "oauth token request: {} returned {}: {}",
+                self.token_url,
+                status,
+                response.text().unwrap_or_default()
+            ));
+        }
+        Ok(response.json()?)
+    }
+}
+
+/// body returns the buffered bytes of a request body, signatures cannot be computed over
+/// streamed bodies such as multipart forms
+fn body(request: &Request) -> Result<&[u8], Error> {
+    match request.body() {
+        Some(b) => b
+            .as_bytes()
+            .ok_or_else(|| anyhow!("request[\"auth\"] cannot sign a streamed request body")),
+        None => Ok(&[]),
+    }
+}
+
+/// host returns the Host header value of a url, omitting the port when it is the scheme default
+fn host(url: &Url) -> String {
+    match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// uri_encode percent encodes every byte outside of the RFC 3986 unreserved characters,
+/// leaving '/' untouched unless encode_slash is set
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' if !encode_slash => "/".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn request(method: Method, url: &str, body: &'static str) -> Request {
+        let mut request = Request::new(method, Url::parse(url).unwrap());
+        if !body.is_empty() {
+            *request.body_mut() = Some(body.into());
+        }
+        request
+    }
+
+    #[test]
+    fn test_basic_bearer() {
+        let mut req = request(Method::GET, "http://localhost:8000/", "");
+        let auth: Auth =
+            serde_json::from_value(json!({"basic": {"username": "user", "password": "pass"}}))
+                .unwrap();
+        auth.apply(&mut req).unwrap();
+        assert_eq!("Basic dXNlcjpwYXNz", req.headers()[header::AUTHORIZATION]);
+
+        let auth: Auth = serde_json::from_value(json!({"bearer": "jWt"})).unwrap();
+        auth.apply(&mut req).unwrap();
+        assert_eq!("Bearer jWt", req.headers()[header::AUTHORIZATION]);
+    }
+
+    #[test]
+    fn test_hmac() {
+        let mut req = request(
+            Method::POST,
+            "http://localhost:8000/hooks?id=1",
+            r#"{"event":"ping"}"#,
+        );
+        let auth: Auth = serde_json::from_value(json!({"hmac": {
+            "secret": "key",
+            "header": "X-Hub-Signature-256",
+            "canonical": "{method} {path}?{query} {timestamp}\n{body}",
+            "prefix": "sha256=",
+            "timestamp_header": "X-Timestamp"
+        }}))
+        .unwrap();
+        let now = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        match auth {
+            Auth::Hmac(hmac) => hmac.apply(&mut req, now).unwrap(),
+            _ => panic!("expected hmac auth"),
+        }
+
+        let expected = hmac_sha256(b"key", b"POST /hooks?id=1 1600000000\n{\"event\":\"ping\"}");
+        assert_eq!("1600000000", req.headers()["X-Timestamp"]);
+        assert_eq!(
+            format!("sha256={}", hex(&expected)),
+            req.headers()["X-Hub-Signature-256"]
+        );
+    }
+
+    #[test]
+    fn test_oauth2_bearer() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for n in 1..=2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !String::from_utf8_lossy(&raw).contains("scope=read") {
+                    let n = stream.read(&mut buf).unwrap();
+                    raw.extend_from_slice(&buf[..n]);
+                }
+                let body = format!(r#"{{"access_token":"token{}","expires_in":3600}}"#, n);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                let raw = String::from_utf8(raw).unwrap();
+                bodies.push(raw.split("\r\n\r\n").nth(1).unwrap().to_string());
+            }
+            bodies
+        });
+
+        let dir = std::env::temp_dir().join("darkroom_test_oauth2_bearer");
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("oauth.json");
+        fs::write(
+            &config_path,
+            json!({
+                "token_url": format!("http://{}/token", addr),
+                "grant_type": "client_credentials",
+                "client_id": "darkroom",
+                "client_secret": "secret",
+                "scope": "read"
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let client = Client::new();
+        let oauth = OAuth2::new(config_path);
+        let shared = oauth.clone();
+        assert_eq!("token1", oauth.bearer(&client).unwrap());
+        // the cached token is returned without another token request
+        assert_eq!("token1", shared.bearer(&client).unwrap());
+        shared.invalidate();
+        assert_eq!("token2", oauth.bearer(&client).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let bodies = server.join().unwrap();
+        assert_eq!(2, bodies.len());
+        assert_eq!(
+            "client_id=darkroom&grant_type=client_credentials&client_secret=secret&scope=read",
+            bodies[0]
+        );
+    }
+
+    // the get-vanilla and get-vanilla-query-order-key-case cases of the AWS SigV4 test suite
+    #[test]
+    fn test_sigv4_authorization() {
+        let sigv4 = SigV4Auth {
+            access_key:    "AKIDEXAMPLE".to_string(),
+            secret_key:    "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+            region:        "us-east-1".to_string(),
+            service:       "service".to_string(),
+        };
+        let now = Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "example.amazonaws.com".parse().unwrap());
+        headers.insert("x-amz-date", "20150830T123600Z".parse().unwrap());
+        let empty_hash = hex(&Sha256::digest(b""));
+
+        let prefix = "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=";
+        assert_eq!(
+            format!(
+                "{}5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+                prefix
+            ),
+            sigv4
+                .authorization(
+                    &Method::GET,
+                    &Url::parse("https://example.amazonaws.com/").unwrap(),
+                    &headers,
+                    &empty_hash,
+                    now,
+                )
+                .unwrap()
+        );
+        assert_eq!(
+            format!(
+                "{}b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500",
+                prefix
+            ),
+            sigv4
+                .authorization(
+                    &Method::GET,
+                    &Url::parse("https://example.amazonaws.com/?Param2=value2&Param1=value1")
+                        .unwrap(),
+                    &headers,
+                    &empty_hash,
+                    now,
+                )
+                .unwrap()
+        );
+    }
+}
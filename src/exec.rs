@@ -0,0 +1,234 @@
+use crate::params::Params;
+use anyhow::{anyhow, Context, Error};
+use filmreel::{frame::Request, response::Response};
+use serde_json::{json, Map, Value};
+use std::{
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::{Child, Command, Output, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// how often a running command is checked for its exit
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// request is used by run_request to run the hydrated uri as a local command, the request body is
+// written to its stdin while stdout becomes the response body and the exit code the status
+pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
+    let uri = req.get_uri();
+    let argv = shlex::split(&uri)
+        .filter(|a| !a.is_empty())
+        .ok_or_else(|| anyhow!("unable to parse request uri field as a command: {}", uri))?;
+
+    let mut command = Command::new(&argv[0]);
+    command
+        .args(&argv[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let etc = req.get_etc();
+    // commands run from the frame directory so that scripts can sit alongside the frame
+    let mut dir: Option<PathBuf> = prm.frame_dir.map(PathBuf::from);
+    match etc.as_ref().and_then(|e| e.get("dir")) {
+        Some(Value::String(d)) => {
+            dir = Some(dir.map_or_else(|| PathBuf::from(d), |f| f.join(d)));
+        }
+        Some(Value::Null) | None => (),
+        _ => return Err(anyhow!("request[\"dir\"] must be a string")),
+    }
+    if let Some(dir) = dir.filter(|d| !d.as_os_str().is_empty()) {
+        command.current_dir(dir);
+    }
+    match etc.as_ref().and_then(|e| e.get("env")) {
+        Some(Value::Object(env)) => {
+            command.envs(env_vars(env)?);
+        }
+        Some(Value::Null) | None => (),
+        _ => return Err(anyhow!("request[\"env\"] must be a key value map")),
+    }
+
+    let stdin = match req.to_val_payload()? {
+        Some(Value::String(s)) => s.into_bytes(),
+        Some(v) => v.to_string().into_bytes(),
+        None => vec![],
+    };
+
+    prm.stopwatch.start();
+    let child = command
+        .spawn()
+        .context(format!("failed to execute {}", argv[0]))?;
+    let output = wait_with_timeout(child, stdin, prm.timeout, &argv[0])?;
+
+    let status = output
+        .status
+        .code()
+        .ok_or_else(|| anyhow!("{} was terminated by a signal", argv[0]))?;
+
+    Ok(Response {
+        body: stdout_body(&output.stdout),
+        etc: Some(json!({ "stderr": String::from_utf8_lossy(&output.stderr) })),
+        status: status as u32,
+        ..Response::default()
+    })
+}
+
+/// wait_with_timeout writes stdin to a spawned child with piped stdio and collects its output,
+/// the child is killed once the timeout in seconds passes unless the timeout is 0
+pub(crate) fn wait_with_timeout(
+    mut child: Child,
+    stdin: Vec<u8>,
+    timeout: u64,
+    name: &str,
+) -> Result<Output, Error> {
+    // stdin is written from its own thread so that a command filling its stdout pipe before
+    // reading the whole of stdin does not deadlock
+    let mut child_stdin = child.stdin.take().expect("piped stdin");
+    let writer = thread::spawn(move || child_stdin.write_all(&stdin));
+    // stdout and stderr are drained while the command runs so that it never blocks on a full pipe
+    let stdout = read_pipe(child.stdout.take().expect("piped stdout"));
+    let stderr = read_pipe(child.stderr.take().expect("piped stderr"));
+
+    let deadline = match timeout {
+        0 => None,
+        t => Some(Instant::now() + Duration::from_secs(t)),
+    };
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!(
+                "{} did not exit within the {}s timeout",
+                name,
+                timeout
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    // a command exiting without reading its stdin closes the pipe early
+    let _ = writer.join();
+    Ok(Output {
+        status,
+        stdout: stdout.join().expect("stdout reader panicked")?,
+        stderr: stderr.join().expect("stderr reader panicked")?,
+    })
+}
+
+/// read_pipe reads an output pipe of a command to its end from its own thread
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        pipe.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+/// env_vars stringifies the values of the `"env"` request map, strings are passed verbatim and
+/// any other value as JSON
+fn env_vars(env: &Map<String, Value>) -> Result<Vec<(String, String)>, Error> {
+    env.iter()
+        .map(|(k, v)| match v {
+            Value::String(s) => Ok((k.clone(), s.clone())),
+            Value::Null => Err(anyhow!("request[\"env\"][\"{}\"] must not be null", k)),
+            v => Ok((k.clone(), v.to_string())),
+        })
+        .collect()
+}
+
+/// stdout_body parses the output of a command as JSON, output that is not JSON is kept as a
+/// string without its trailing newline
+fn stdout_body(stdout: &[u8]) -> Option<Value> {
+    if stdout.is_empty() {
+        return None;
+    }
+    if let Ok(value) = serde_json::from_slice(stdout) {
+        return Some(value);
+    }
+    let text = String::from_utf8_lossy(stdout);
+    let text = text
+        .strip_suffix('\n')
+        .map(|t| t.strip_suffix('\r').unwrap_or(t))
+        .unwrap_or(&text);
+    Some(Value::String(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest(
+        stdout,
+        expected,
+        case(b"", None),
+        case(b"{\"id\": 1}\n", Some(json!({"id": 1}))),
+        case(b"[1, 2]", Some(json!([1, 2]))),
+        case(b"migrated 3 tables\n", Some(json!("migrated 3 tables"))),
+        case(b"line one\r\nline two\r\n", Some(json!("line one\r\nline two")))
+    )]
+    fn test_stdout_body(stdout: &[u8], expected: Option<Value>) {
+        assert_eq!(expected, stdout_body(stdout));
+    }
+
+    #[test]
+    fn test_request() {
+        let req: Request = serde_json::from_value(json!({
+            "body": {"name": "ben"},
+            "uri": "sh -c 'cat; echo \"$GREETING\" >&2; exit 3'",
+            "env": {"GREETING": "hello"}
+        }))
+        .unwrap();
+        let response = request(Params::default(), req).unwrap();
+
+        assert_eq!(Some(json!({"name": "ben"})), response.body);
+        assert_eq!(Some(json!({"stderr": "hello\n"})), response.etc);
+        assert_eq!(3, response.status);
+    }
+
+    #[test]
+    fn test_request_dir() {
+        let dir = std::env::temp_dir().join("darkroom_test_exec_dir");
+        std::fs::create_dir_all(dir.join("scripts")).unwrap();
+        let expected = dir.join("scripts").canonicalize().unwrap();
+        let req: Request = serde_json::from_value(json!({"uri": "pwd", "dir": "scripts"})).unwrap();
+        let prm = Params {
+            frame_dir: Some(&dir),
+            ..Params::default()
+        };
+        let response = request(prm, req).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Some(json!(expected)), response.body);
+        assert_eq!(0, response.status);
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let req: Request = serde_json::from_value(json!({"uri": "sleep 10"})).unwrap();
+        let prm = Params {
+            timeout: 1,
+            ..Params::default()
+        };
+        let start = Instant::now();
+        let err = request(prm, req).unwrap_err();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!("sleep did not exit within the 1s timeout", err.to_string());
+    }
+
+    #[rstest(
+        req,
+        case(json!({"uri": ""})),
+        case(json!({"uri": "echo 'unterminated"})),
+        case(json!({"uri": "echo", "env": ["A=1"]})),
+        case(json!({"uri": "darkroom-missing-command"}))
+    )]
+    fn test_request_err(req: Value) {
+        let req: Request = serde_json::from_value(req).unwrap();
+        assert!(request(Params::default(), req).is_err());
+    }
+}
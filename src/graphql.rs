@@ -0,0 +1,213 @@
+use crate::{http, params::Params};
+use anyhow::{anyhow, Error};
+use filmreel::{frame::Request, response::Response};
+use serde_json::{json, Map, Value};
+
+/// the request fields forming the GraphQL payload rather than options of the HTTP request
+const QUERY: &str = "query";
+const VARIABLES: &str = "variables";
+const OPERATION_NAME: &str = "operationName";
+
+// request is used by run_request to POST a GraphQL operation over HTTP, the returned `data` is
+// kept as the response body while `errors` are compared separately as `"response"."errors"`
+pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
+    let response = http::request(prm, to_http_request(req)?)?;
+    Ok(from_http_response(response))
+}
+
+/// to_http_request moves the `"query"`, `"variables"` and `"operationName"` of a hydrated GraphQL
+/// request into the body of a JSON POST request to the frame uri
+fn to_http_request(req: Request) -> Result<Request, Error> {
+    if req.to_val_payload()?.is_some() {
+        return Err(anyhow!(
+            "GraphQL requests are built from request[\"query\"], request[\"variables\"] and \
+             request[\"operationName\"] rather than a request body"
+        ));
+    }
+    let mut req = match serde_json::to_value(req)? {
+        Value::Object(map) => map,
+        _ => return Err(anyhow!("unable to parse GraphQL request")),
+    };
+
+    let mut payload = Map::new();
+    match req.remove(QUERY) {
+        Some(query @ Value::String(_)) => payload.insert(QUERY.to_string(), query),
+        _ => {
+            return Err(anyhow!(
+                "request[\"query\"] must be a GraphQL document string"
+            ))
+        }
+    };
+    match req.remove(VARIABLES) {
+        Some(variables @ Value::Object(_)) => {
+            payload.insert(VARIABLES.to_string(), variables);
+        }
+        Some(Value::Null) | None => (),
+        _ => return Err(anyhow!("request[\"variables\"] must be a key value map")),
+    }
+    match req.remove(OPERATION_NAME) {
+        Some(name @ Value::String(_)) => {
+            payload.insert(OPERATION_NAME.to_string(), name);
+        }
+        Some(Value::Null) | None => (),
+        _ => return Err(anyhow!("request[\"operationName\"] must be a string")),
+    }
+
+    let uri = match req.get("uri") {
+        Some(Value::String(uri)) => format!("POST {}", uri),
+        _ => "POST ".to_string(),
+    };
+    req.insert("uri".to_string(), Value::String(uri));
+    req.insert("body".to_string(), Value::Object(payload));
+    req.entry("content_type")
+        .or_insert_with(|| json!("application/json"));
+    Ok(serde_json::from_value(Value::Object(req))?)
+}
+
+/// from_http_response splits a GraphQL response into its `data`, kept as the response body, and
+/// its `errors`, any `extensions` are exposed as `"response"."extensions"`. Bodies that are not a
+/// GraphQL response, such as a gateway error, are left untouched
+fn from_http_response(mut response: Response) -> Response {
+    let mut body = match response.body.take() {
+        Some(Value::Object(map)) if map.contains_key("data") || map.contains_key("errors") => map,
+        body => {
+            response.body = body;
+            return response;
+        }
+    };
+
+    response.body = body.remove("data").filter(|d| !d.is_null());
+    response.errors = body
+        .remove("errors")
+        .filter(|e| !matches!(e, Value::Null) && e != &json!([]));
+    if let (Some(extensions), Some(Value::Object(etc))) =
+        (body.remove("extensions"), response.etc.as_mut())
+    {
+        etc.insert("extensions".to_string(), extensions);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_to_http_request() {
+        let req: Request = serde_json::from_value(json!({
+            "query": "query User($id: ID!) { user(id: $id) { name } }",
+            "variables": {"id": "USER_1"},
+            "operationName": "User",
+            "header": {"Authorization": "Bearer jWt"},
+            "uri": "/graphql"
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::from_value::<Request>(json!({
+                "body": {
+                    "query": "query User($id: ID!) { user(id: $id) { name } }",
+                    "variables": {"id": "USER_1"},
+                    "operationName": "User"
+                },
+                "content_type": "application/json",
+                "header": {"Authorization": "Bearer jWt"},
+                "uri": "POST /graphql"
+            }))
+            .unwrap(),
+            to_http_request(req).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_http_request_err() {
+        for req in [
+            json!({"uri": "/graphql"}),
+            json!({"query": {"user": "name"}, "uri": "/graphql"}),
+            json!({"query": "{ user }", "variables": ["id"], "uri": "/graphql"}),
+            json!({"query": "{ user }", "body": {"query": "{ user }"}, "uri": "/graphql"}),
+        ]
+        .iter()
+        {
+            let req: Request = serde_json::from_value(req.clone()).unwrap();
+            assert!(to_http_request(req).is_err());
+        }
+    }
+
+    #[test]
+    fn test_request() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&raw).ends_with('}') {
+                let n = stream.read(&mut buf).unwrap();
+                raw.extend_from_slice(&buf[..n]);
+            }
+            let body = r#"{"data": {"user": null}, "errors": [{"message": "user not found", "path": ["user"]}]}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            String::from_utf8(raw).unwrap()
+        });
+
+        let req: Request = serde_json::from_value(json!({
+            "query": "query User($id: ID!) { user(id: $id) { name } }",
+            "variables": {"id": "USER_1"},
+            "uri": "/graphql"
+        }))
+        .unwrap();
+        let prm = Params {
+            address,
+            ..Params::default()
+        };
+        let response = request(prm, req).unwrap();
+        let raw = server.join().unwrap();
+
+        assert!(raw.starts_with("POST /graphql HTTP/1.1"));
+        assert!(raw.contains("content-type: application/json"));
+        let (_, sent) = raw.split_once("\r\n\r\n").unwrap();
+        assert_eq!(
+            json!({
+                "query": "query User($id: ID!) { user(id: $id) { name } }",
+                "variables": {"id": "USER_1"}
+            }),
+            serde_json::from_str::<Value>(sent).unwrap()
+        );
+        assert_eq!(Some(json!({"user": null})), response.body);
+        assert_eq!(
+            Some(json!([{"message": "user not found", "path": ["user"]}])),
+            response.errors
+        );
+        assert_eq!(200, response.status);
+    }
+
+    #[test]
+    fn test_from_http_response() {
+        let response = from_http_response(Response {
+            body: Some(json!({
+                "data": {"user": {"name": "Ben"}},
+                "errors": [],
+                "extensions": {"cost": 1}
+            })),
+            status: 200,
+            ..Response::default()
+        });
+        assert_eq!(Some(json!({"user": {"name": "Ben"}})), response.body);
+        assert_eq!(None, response.errors);
+        assert_eq!(Some(json!({"extensions": {"cost": 1}})), response.etc);
+
+        let response = from_http_response(Response {
+            body: Some(json!("Bad Gateway")),
+            status: 502,
+            ..Response::default()
+        });
+        assert_eq!(Some(json!("Bad Gateway")), response.body);
+    }
+}
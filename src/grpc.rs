@@ -1,138 +1,1085 @@
-use crate::params::{iter_path_args, Params};
+use crate::params::Params;
 use anyhow::{anyhow, Context, Error};
 use filmreel::{frame::Request, response::Response};
+use http::{uri::PathAndQuery, Uri};
 use lazy_static::lazy_static;
-use serde::Deserialize;
-use serde_json::json;
-use std::{ffi::OsStr, path::PathBuf, process::Command};
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor, SerializeOptions,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+use tonic::{
+    codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder},
+    codegen::tokio_stream,
+    metadata::{KeyRef, MetadataKey, MetadataMap, MetadataValue},
+    transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity},
+    Code, Status,
+};
 
-/// Checks to see if grpcurl is in the system path
-pub fn validate_grpcurl() -> Result<(), Error> {
-    lazy_static! {
-        static ref GRPCURL: which::Result<PathBuf> = which::which("grpcurl");
+mod details;
+pub mod grpcurl;
+mod reflection;
+mod tunnel;
+
+use tunnel::Tunnel;
+
+/// source of the descriptors of a DescriptorPool
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PoolKey {
+    /// import paths, proto files and protoset files given to a run
+    Files(Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>),
+    /// address and service resolved through server reflection
+    Reflection(String, String),
+}
+
+lazy_static! {
+    /// descriptor pools compiled for a run
+    static ref POOLS: Mutex<HashMap<PoolKey, DescriptorPool>> = Mutex::new(HashMap::new());
+}
+
+/// request parses a Frame Request and a Params object to send a gRPC payload using the native
+/// client, messages are encoded dynamically from the protos or protosets of the Params object or
+/// from the descriptors returned by server reflection. `grpcurl` is used instead when
+/// `--grpcurl` is given.
+///
+/// Client and bidirectional streams send every message of a request body array in order, the
+/// messages of server and bidirectional streams are returned as a response body array in the
+/// order they were received
+pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
+    if prm.grpcurl {
+        return grpcurl::request(prm, req);
     }
-    if !GRPCURL.is_ok() {
-        return Err(anyhow!("`grpcurl` was not found! Check your PATH!"));
+
+    let uri = req.get_uri();
+    let (service, method) = split_uri(&uri)?;
+    let payload = req.to_val_payload()?.unwrap_or_else(|| json!({}));
+    let metadata = build_metadata(&prm)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let (method, reply) = runtime.block_on(async {
+        let channel = connect(&prm).await?;
+        let pool = descriptor_pool(&prm, &channel, service, &metadata).await?;
+        let method = method_descriptor(&pool, service, method)?;
+        let payload = match payload {
+            Value::Array(values) if method.is_client_streaming() => values,
+            value => vec![value],
+        };
+        let messages = payload
+            .into_iter()
+            .map(|v| DynamicMessage::deserialize(method.input(), v))
+            .collect::<Result<Vec<_>, _>>()
+            .context(format!("request body of {}", method.full_name()))?;
+        let mut request = tonic::Request::new(messages);
+        *request.metadata_mut() = metadata;
+        prm.stopwatch.start();
+        let reply = call(channel, &prm, &method, request).await?;
+        prm.stopwatch.stop();
+        Ok::<_, Error>((method, reply))
+    })?;
+
+    let body = match &reply.messages {
+        Ok(messages) if method.is_server_streaming() => Value::Array(
+            messages
+                .iter()
+                .map(to_json)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Ok(messages) => match messages.first() {
+            Some(message) if messages.len() == 1 => to_json(message)?,
+            _ => {
+                return Err(anyhow!(
+                    "{} returned {} messages rather than one",
+                    method.full_name(),
+                    messages.len()
+                ))
+            }
+        },
+        // create frame response from the returned gRPC status
+        Err(status) => Value::String(status.message().to_string()),
+    };
+    let mut etc = json!({ "trailers": metadata_to_value(&reply.trailers) });
+    // the full status of a failed call is exposed as "response"."error"
+    if let Err(status) = &reply.messages {
+        etc["error"] = details::status_to_value(status, method.parent_pool());
     }
-    Ok(())
+    Ok(Response {
+        body: Some(body),
+        header: Some(metadata_to_value(&reply.header)),
+        etc: Some(etc),
+        status: reply.status as u32,
+        ..Response::default()
+    })
 }
 
-/// request parses a Frame Request and a Params object to send a gRPC payload using `grpcurl`
-/// the command line tool
-pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
-    validate_grpcurl().context("grpcurl request failure")?;
+fn to_json(message: &DynamicMessage) -> Result<Value, Error> {
+    Ok(message.serialize_with_options(serde_json::value::Serializer, &SerializeOptions::new())?)
+}
 
-    let mut flags: Vec<&OsStr> = vec![OsStr::new("-format-error")];
+/// Reply holds the response metadata and the messages or status returned by a call
+struct Reply {
+    header:   MetadataMap,
+    messages: Result<Vec<DynamicMessage>, Status>,
+    status:   Code,
+    trailers: MetadataMap,
+}
 
-    if !prm.tls {
-        flags.push(OsStr::new("-plaintext"));
+/// call streams the request messages over the channel and collects every message received until
+/// the server closes the stream. A returned gRPC status is kept in the Reply so that it can be
+/// compared against the frame response, responses holding only trailers have their metadata
+/// kept as trailers
+async fn call(
+    channel: Channel,
+    prm: &Params<'_>,
+    method: &MethodDescriptor,
+    request: tonic::Request<Vec<DynamicMessage>>,
+) -> Result<Reply, Error> {
+    let mut client = tonic::client::Grpc::new(channel);
+    client
+        .ready()
+        .await
+        .context(format!("{} is not ready", prm.address))?;
+    let path = PathAndQuery::try_from(format!(
+        "/{}/{}",
+        method.parent_service().full_name(),
+        method.name()
+    ))?;
+    let codec = DynamicCodec(method.output());
+    // every call is made as a stream, keeping the response headers apart from the trailers
+    let request = request.map(tokio_stream::iter);
+    let (header, mut stream) = match client.streaming(request, path, codec).await {
+        Ok(response) => {
+            let (header, stream, _) = response.into_parts();
+            (header, stream)
+        }
+        Err(status) => return Ok(Reply::from_status(MetadataMap::new(), status)),
+    };
+
+    let mut messages = vec![];
+    loop {
+        match stream.message().await {
+            Ok(Some(message)) => messages.push(message),
+            Ok(None) => break,
+            Err(status) => return Ok(Reply::from_status(header, status)),
+        }
+    }
+    let mut trailers = match stream.trailers().await {
+        Ok(trailers) => trailers.unwrap_or_default(),
+        Err(status) => return Ok(Reply::from_status(header, status)),
+    };
+    for reserved in ["grpc-status", "grpc-message", "grpc-status-details-bin"] {
+        trailers.remove(reserved);
     }
+    Ok(Reply {
+        header,
+        messages: Ok(messages),
+        status: Code::Ok,
+        trailers,
+    })
+}
 
-    // prepend "-import-path" to every protos PathBuf provided
-    if let Some(proto_path) = prm.proto_path {
-        flags.extend(iter_path_args(
-            OsStr::new("-import-path"),
-            proto_path.iter().map(OsStr::new),
-        ));
+impl Reply {
+    fn from_status(header: MetadataMap, status: Status) -> Self {
+        Self {
+            header,
+            status: status.code(),
+            trailers: status.metadata().clone(),
+            messages: Err(status),
+        }
     }
+}
 
-    // prepend "-proto" to every protos PathBuf provided
-    if let Some(protos) = prm.proto {
-        flags.extend(iter_path_args(
-            OsStr::new("-proto"),
-            protos.iter().map(OsStr::new),
-        ));
+async fn connect(prm: &Params<'_>) -> Result<Channel, Error> {
+    let tls_config = &prm.tls_config;
+    // any TLS option implies a TLS connection
+    let tls = prm.tls || tls_config.is_set();
+
+    let uri = match prm.address.contains("://") {
+        true => prm.address.clone(),
+        false if tls => format!("https://{}", prm.address),
+        false => format!("http://{}", prm.address),
+    };
+    let parsed = Uri::try_from(&uri).context(uri.clone())?;
+    let authority = parsed
+        .authority()
+        .ok_or_else(|| anyhow!("gRPC address without a host: {}", prm.address))?;
+    // certificates that are not verified are negotiated by the Tunnel rather than by tonic, which
+    // would otherwise negotiate TLS again over an https uri
+    let uri = match tls_config.is_insecure() {
+        true => format!("http://{}", authority),
+        false => uri,
+    };
+    let mut endpoint = Endpoint::from_shared(uri.clone()).context(uri)?;
+    if prm.timeout != 0 {
+        endpoint = endpoint
+            .connect_timeout(Duration::from_secs(prm.timeout))
+            .timeout(Duration::from_secs(prm.timeout));
     }
+    if tls && !tls_config.is_insecure() {
+        let mut config = ClientTlsConfig::new();
+        if let Some(cacert) = &tls_config.cacert {
+            config = config.ca_certificate(Certificate::from_pem(read(cacert, "cacert")?));
+        }
+        match (&tls_config.cert, &tls_config.key) {
+            (Some(cert), Some(key)) => {
+                config =
+                    config.identity(Identity::from_pem(read(cert, "cert")?, read(key, "key")?));
+            }
+            (None, None) => (),
+            _ => return Err(anyhow!("a client cert and key must be provided together")),
+        }
+        endpoint = endpoint.tls_config(config)?;
+    }
+
+    let connection = match prm.proxy.is_some() || tls_config.is_insecure() {
+        true => {
+            let tunnel = Tunnel::new(prm.proxy.as_deref(), tls_config, authority.host())?;
+            endpoint.connect_with_connector(tunnel).await
+        }
+        false => endpoint.connect().await,
+    };
+    connection.context(format!("failed to connect to {}", prm.address))
+}
 
-    let headers = prm.header.as_ref().map(|h| h.replace("\"", ""));
+fn read(path: &Path, name: &str) -> Result<Vec<u8>, Error> {
+    fs::read(path).context(format!("{}: {}", name, path.display()))
+}
 
-    if let Some(h) = &headers {
-        flags.push(OsStr::new("-H"));
-        flags.push(OsStr::new(h));
+/// descriptor_pool returns the descriptors of the protos and protosets given to a run, the
+/// descriptors of the service are requested through server reflection, with the metadata of the
+/// call, when neither protos nor protosets are given
+async fn descriptor_pool(
+    prm: &Params<'_>,
+    channel: &Channel,
+    service: &str,
+    metadata: &MetadataMap,
+) -> Result<DescriptorPool, Error> {
+    if let Some(pool) = file_pool(prm)? {
+        return Ok(pool);
+    }
+    let key = PoolKey::Reflection(prm.address.clone(), service.to_string());
+    if let Some(pool) = cached_pool(&key) {
+        return Ok(pool);
     }
+    let pool = reflection::descriptor_pool(channel.clone(), service, metadata.clone())
+        .await
+        .context(format!(
+            "no --proto or --protoset given, server reflection of {} failed",
+            service
+        ))?;
+    cache_pool(key, pool.clone());
+    Ok(pool)
+}
 
-    let req_cmd = Command::new("grpcurl")
-        .args(flags)
-        .arg("-connect-timeout")
-        .arg(format!("{:.1}", prm.timeout as f32))
-        .arg("-d")
-        .arg(req.to_payload()?)
-        .arg(&prm.address)
-        .arg(req.get_uri())
-        .output()
-        .context("failed to execute grpcurl process")?;
+/// file_pool decodes the protosets and compiles the proto files given to a run, resolving imports
+/// from the `--proto-dir` paths or the current directory when no paths are given. None is returned
+/// when neither protos nor protosets are given
+fn file_pool(prm: &Params) -> Result<Option<DescriptorPool>, Error> {
+    if prm.proto.is_empty() && prm.protoset.is_empty() {
+        return Ok(None);
+    }
+    let import_paths = match prm.proto_path.len() {
+        0 => vec![PathBuf::from(".")],
+        _ => prm.proto_path.clone(),
+    };
+    let key = PoolKey::Files(import_paths, prm.proto.clone(), prm.protoset.clone());
+    if let Some(pool) = cached_pool(&key) {
+        return Ok(Some(pool));
+    }
 
-    let response = match req_cmd.status.code() {
-        Some(0) => Response {
-            body:       serde_json::from_slice(&req_cmd.stdout)?,
-            status:     0,
-            etc:        Some(json!({})),
-            validation: None,
-        },
-        Some(_) => {
-            let err: ResponseError = serde_json::from_slice(&req_cmd.stderr).map_err(|_| {
-                // if we fail to map to a serde struct, stringingfy stderr bytes and cast to anyhow error
-                String::from_utf8(req_cmd.stderr)
-                    .map_err(Error::from)
-                    .map(|v| anyhow!(v))
-                    .context("grpcurl error")
-                    .unwrap_or_else(|e| e)
-            })?;
-            // create frame response from deserialized grpcurl error
-            Response {
-                body:       Some(serde_json::Value::String(err.message)),
-                status:     err.code,
-                etc:        Some(json!({})),
-                validation: None,
+    let mut pool = DescriptorPool::new();
+    for protoset in &prm.protoset {
+        pool.decode_file_descriptor_set(read(protoset, "protoset")?.as_slice())
+            .context(format!("protoset: {}", protoset.display()))?;
+    }
+    if let PoolKey::Files(import_paths, protos, _) = &key {
+        if !protos.is_empty() {
+            pool.add_file_descriptor_set(
+                protox::Compiler::new(import_paths)?
+                    .include_imports(true)
+                    .open_files(protos)?
+                    .file_descriptor_set(),
+            )?;
+        }
+    }
+    cache_pool(key, pool.clone());
+    Ok(Some(pool))
+}
+
+fn cached_pool(key: &PoolKey) -> Option<DescriptorPool> {
+    POOLS
+        .lock()
+        .expect("descriptor pool lock poisoned")
+        .get(key)
+        .cloned()
+}
+
+fn cache_pool(key: PoolKey, pool: DescriptorPool) {
+    POOLS
+        .lock()
+        .expect("descriptor pool lock poisoned")
+        .insert(key, pool);
+}
+
+/// split_uri splits a `package.Service/Method` or `package.Service.Method` uri into its service
+/// and method names
+fn split_uri(uri: &str) -> Result<(&str, &str), Error> {
+    let split = uri
+        .rfind('/')
+        .or_else(|| uri.rfind('.'))
+        .ok_or_else(|| anyhow!("gRPC uri must be formatted as package.Service/Method"))?;
+    Ok((&uri[..split], &uri[split + 1..]))
+}
+
+/// method_descriptor finds a method of a service in the DescriptorPool
+fn method_descriptor(
+    pool: &DescriptorPool,
+    service: &str,
+    method: &str,
+) -> Result<MethodDescriptor, Error> {
+    pool.get_service_by_name(service)
+        .ok_or_else(|| anyhow!("gRPC service not found: {}", service))?
+        .methods()
+        .find(|m| m.name() == method)
+        .ok_or_else(|| anyhow!("gRPC method not found: {}/{}", service, method))
+}
+
+/// build_metadata converts the Params header into gRPC request metadata, binary values of keys
+/// ending in `-bin` are given as base64 strings
+fn build_metadata(prm: &Params) -> Result<MetadataMap, Error> {
+    let mut metadata = MetadataMap::new();
+    if let Some(header) = &prm.header {
+        for (key, value) in metadata_entries(header)? {
+            let key = key.trim().to_lowercase();
+            if key.ends_with("-bin") {
+                let bytes = base64::decode_config(
+                    value.trim().trim_end_matches('='),
+                    base64::STANDARD_NO_PAD,
+                )
+                .context(format!("{} metadata base64 decode failure", key))?;
+                metadata.append_bin(
+                    MetadataKey::from_bytes(key.as_bytes())?,
+                    MetadataValue::from_bytes(&bytes),
+                );
+            } else {
+                metadata.append(
+                    MetadataKey::from_bytes(key.as_bytes())?,
+                    MetadataValue::try_from(value.trim())?,
+                );
             }
         }
-        None => return Err(anyhow!("grpcurl response code was <None>")),
+    }
+
+    if let Some(oauth) = &prm.oauth {
+        let client = prm.session.client(&prm.tls_config, prm.proxy.as_deref())?;
+        metadata.insert(
+            "authorization",
+            MetadataValue::try_from(format!("Bearer {}", oauth.bearer(&client)?))?,
+        );
+    }
+    Ok(metadata)
+}
+
+/// metadata_entries lists the key value pairs of a header given as a `"key: value"` string, an
+/// array of such strings or a JSON object whose array values send a key once per item
+fn metadata_entries(header: &str) -> Result<Vec<(String, String)>, Error> {
+    let to_string = |v: Value| match v {
+        Value::String(s) => s,
+        v => v.to_string(),
+    };
+    let split = |h: &str| -> Result<(String, String), Error> {
+        h.split_once(':')
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .ok_or_else(|| anyhow!("gRPC header must be formatted as \"key: value\""))
     };
-    Ok(response)
+
+    match serde_json::from_str(header) {
+        Ok(Value::Object(map)) => Ok(map
+            .into_iter()
+            .flat_map(|(k, v)| match v {
+                Value::Array(values) => values
+                    .into_iter()
+                    .map(|v| (k.clone(), to_string(v)))
+                    .collect(),
+                v => vec![(k, to_string(v))],
+            })
+            .collect()),
+        Ok(Value::Array(values)) => values.into_iter().map(|v| split(&to_string(v))).collect(),
+        Ok(Value::String(h)) => Ok(vec![split(&h)?]),
+        _ => Ok(vec![split(&header.replace("\"", ""))?]),
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-struct ResponseError {
-    code:    u32,
-    message: String,
+/// metadata_to_value converts gRPC metadata into a JSON object keyed by metadata name, binary
+/// values are base64 encoded and keys repeated in the metadata are collected into an array
+fn metadata_to_value(metadata: &MetadataMap) -> Value {
+    let mut map = Map::new();
+    for key in metadata.keys() {
+        let (name, mut values): (&str, Vec<Value>) = match key {
+            KeyRef::Ascii(k) => (
+                k.as_str(),
+                metadata
+                    .get_all(k)
+                    .iter()
+                    .map(|v| {
+                        Value::String(String::from_utf8_lossy(v.as_encoded_bytes()).into_owned())
+                    })
+                    .collect(),
+            ),
+            KeyRef::Binary(k) => (
+                k.as_str(),
+                metadata
+                    .get_all_bin(k)
+                    .iter()
+                    .map(|v| Value::String(base64::encode(v.to_bytes().unwrap_or_default())))
+                    .collect(),
+            ),
+        };
+        let value = match values.len() {
+            1 => values.remove(0),
+            _ => Value::Array(values),
+        };
+        map.insert(name.to_string(), value);
+    }
+    Value::Object(map)
+}
+
+/// DynamicCodec encodes any DynamicMessage and decodes messages using the MessageDescriptor it
+/// holds
+#[derive(Clone)]
+struct DynamicCodec(MessageDescriptor);
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = Self;
+    type Decoder = Self;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        self.clone()
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        self.clone()
+    }
+}
+
+impl Encoder for DynamicCodec {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|e| Status::internal(format!("failed to encode message: {}", e)))
+    }
+}
+
+impl Decoder for DynamicCodec {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|e| Status::internal(format!("failed to decode message: {}", e)))
+    }
 }
 
 #[cfg(test)]
-mod serde_tests {
+mod tests {
     use super::*;
-    use serde_json;
+    use prost_reflect::ReflectMessage;
+    use reflection::{
+        ErrorResponse, FileDescriptorResponse, MessageRequest, MessageResponse,
+        ServerReflectionRequest, ServerReflectionResponse,
+    };
+    use rstest::*;
+    use std::thread;
+    use tonic::{
+        codec::{ProstCodec, Streaming},
+        codegen::{
+            empty_body, http, tokio_stream, Body, BoxFuture, BoxStream, Context, Poll, Service,
+            StdError,
+        },
+        server::{Grpc, NamedService, StreamingService},
+        transport::{server::TcpIncoming, Server, ServerTlsConfig},
+        Code,
+    };
+
+    const USERS_PROTO: &str = r#"
+syntax = "proto3";
+
+package darkroom.test;
+
+import "google/protobuf/empty.proto";
 
-    const INTERNAL_ERROR: &str = r#"{
-  "code": 13,
-  "message": "input cannot be empty"
-}"#;
-    const AUTH_ERROR: &str = r#"{
-  "code": 16,
-  "message": "rpc error: code = Unauthenticated desc = Empty JWT token"
-}"#;
+service Users {
+  rpc Create(CreateRequest) returns (User);
+  rpc Ping(google.protobuf.Empty) returns (google.protobuf.Empty);
+  // returns the created user once for every year of its age
+  rpc Repeat(CreateRequest) returns (stream User);
+  // returns the last user created
+  rpc Last(stream CreateRequest) returns (User);
+  rpc Import(stream CreateRequest) returns (stream User);
+}
+
+message CreateRequest {
+  string name = 1;
+  int64 age = 2;
+}
+
+message User {
+  string id = 1;
+  string name = 2;
+  int64 age = 3;
+}
+"#;
+
+    type Handler = fn(DynamicMessage) -> Result<DynamicMessage, Status>;
+
+    /// TestServer serves every method of darkroom.test.Users using a single handler applied to
+    /// each message received
+    #[derive(Clone)]
+    struct TestServer {
+        pool:    DescriptorPool,
+        handler: Handler,
+    }
+
+    impl NamedService for TestServer {
+        const NAME: &'static str = "darkroom.test.Users";
+    }
+
+    struct TestMethod(MethodDescriptor, Handler);
+
+    impl StreamingService<DynamicMessage> for TestMethod {
+        type Response = DynamicMessage;
+        type ResponseStream = BoxStream<DynamicMessage>;
+        type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+        // handlers reply with a tonic Status as the server of a real service would
+        #[allow(clippy::result_large_err)]
+        fn call(&mut self, request: tonic::Request<Streaming<DynamicMessage>>) -> Self::Future {
+            let (method, handler) = (self.0.clone(), self.1);
+            Box::pin(async move {
+                // echo the request id as a response header or as a trailer of an error status
+                let request_id = request.metadata().get("x-request-id").cloned();
+                let mut stream = request.into_inner();
+                let mut result: Result<Vec<DynamicMessage>, Status> = Ok(vec![]);
+                while let Some(message) = stream.message().await? {
+                    result = result.and_then(|mut users| {
+                        users.push(handler(message)?);
+                        Ok(users)
+                    });
+                }
+                let mut result = result.map(|users| match method.name() {
+                    "Repeat" => {
+                        let age = users[0].get_field_by_name("age").unwrap().as_i64().unwrap();
+                        vec![users[0].clone(); age as usize]
+                    }
+                    "Last" => users.into_iter().last().into_iter().collect(),
+                    _ => users,
+                });
+                if let (Err(s), Some(id)) = (&mut result, &request_id) {
+                    s.metadata_mut().insert("x-request-id", id.clone());
+                }
+                let users: Self::ResponseStream =
+                    Box::pin(tokio_stream::iter(result?.into_iter().map(Ok)));
+                let mut response = tonic::Response::new(users);
+                if let Some(id) = request_id {
+                    response.metadata_mut().insert("x-request-id", id);
+                }
+                Ok(response)
+            })
+        }
+    }
+
+    impl<B> Service<http::Request<B>> for TestServer
+    where
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let method = split_uri(req.uri().path().trim_start_matches('/'))
+                .and_then(|(service, method)| method_descriptor(&self.pool, service, method));
+            let handler = self.handler;
+            Box::pin(async move {
+                Ok(match method {
+                    Ok(m) => {
+                        let mut grpc = Grpc::new(DynamicCodec(m.input()));
+                        grpc.streaming(TestMethod(m, handler), req).await
+                    }
+                    Err(_) => http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap(),
+                })
+            })
+        }
+    }
+
+    /// ReflectionServer answers v1alpha server reflection requests with the files of its pool,
+    /// requests carrying an authorization other than `Bearer darkroom` are refused
+    #[derive(Clone)]
+    struct ReflectionServer(DescriptorPool);
+
+    impl NamedService for ReflectionServer {
+        const NAME: &'static str = "grpc.reflection.v1alpha.ServerReflection";
+    }
+
+    struct ReflectionMethod(DescriptorPool);
+
+    impl StreamingService<ServerReflectionRequest> for ReflectionMethod {
+        type Response = ServerReflectionResponse;
+        type ResponseStream = BoxStream<ServerReflectionResponse>;
+        type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+        fn call(
+            &mut self,
+            request: tonic::Request<Streaming<ServerReflectionRequest>>,
+        ) -> Self::Future {
+            let pool = self.0.clone();
+            Box::pin(async move {
+                if let Some(bearer) = request.metadata().get("authorization") {
+                    if bearer != "Bearer darkroom" {
+                        return Err(Status::unauthenticated("invalid bearer"));
+                    }
+                }
+                let mut stream = request.into_inner();
+                let mut responses = vec![];
+                while let Some(request) = stream.message().await? {
+                    let file = match request.message_request {
+                        Some(MessageRequest::FileContainingSymbol(symbol)) => {
+                            pool.get_service_by_name(&symbol).map(|s| s.parent_file())
+                        }
+                        Some(MessageRequest::FileByFilename(name)) => pool.get_file_by_name(&name),
+                        None => None,
+                    };
+                    let message_response = match file {
+                        Some(f) => {
+                            MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+                                file_descriptor_proto: vec![f
+                                    .file_descriptor_proto()
+                                    .encode_to_vec()],
+                            })
+                        }
+                        None => MessageResponse::ErrorResponse(ErrorResponse {
+                            error_code:    Code::NotFound as i32,
+                            error_message: "not found".to_string(),
+                        }),
+                    };
+                    responses.push(Ok(ServerReflectionResponse {
+                        message_response: Some(message_response),
+                    }));
+                }
+                let responses: Self::ResponseStream = Box::pin(tokio_stream::iter(responses));
+                Ok(tonic::Response::new(responses))
+            })
+        }
+    }
+
+    impl<B> Service<http::Request<B>> for ReflectionServer
+    where
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let method = ReflectionMethod(self.0.clone());
+            Box::pin(async move {
+                let mut grpc = Grpc::new(ProstCodec::default());
+                Ok(grpc.streaming(method, req).await)
+            })
+        }
+    }
+
+    /// serve starts a TestServer on a random port returning its address along with the path of
+    /// the proto file it serves
+    fn serve(name: &str, handler: Handler) -> (String, PathBuf) {
+        serve_tls(name, handler, None)
+    }
+
+    /// serve_tls starts a TestServer accepting TLS connections when a ServerTlsConfig is given
+    fn serve_tls(name: &str, handler: Handler, tls: Option<ServerTlsConfig>) -> (String, PathBuf) {
+        let dir = std::env::temp_dir().join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let proto = dir.join("users.proto");
+        fs::write(&proto, USERS_PROTO).unwrap();
+        let pool = protox::Compiler::new([&dir])
+            .unwrap()
+            .open_file(&proto)
+            .unwrap()
+            .descriptor_pool();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                sender.send(listener.local_addr().unwrap()).unwrap();
+                let mut builder = Server::builder();
+                if let Some(tls) = tls {
+                    builder = builder.tls_config(tls).unwrap();
+                }
+                builder
+                    .add_service(ReflectionServer(pool.clone()))
+                    .add_service(TestServer { pool, handler })
+                    .serve_with_incoming(TcpIncoming::from_listener(listener, true, None).unwrap())
+                    .await
+                    .unwrap();
+            });
+        });
+        (receiver.recv().unwrap().to_string(), proto)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn create_user(request: DynamicMessage) -> Result<DynamicMessage, Status> {
+        let name = request.get_field_by_name("name").unwrap();
+        if name.as_str() == Some("") {
+            return Err(Status::invalid_argument("name is required"));
+        }
+        let mut user = DynamicMessage::new(
+            request
+                .descriptor()
+                .parent_pool()
+                .get_message_by_name("darkroom.test.User")
+                .unwrap(),
+        );
+        user.set_field_by_name("id", prost_reflect::Value::String("007".to_string()));
+        user.set_field_by_name("name", name.into_owned());
+        user.set_field_by_name(
+            "age",
+            request.get_field_by_name("age").unwrap().into_owned(),
+        );
+        Ok(user)
+    }
 
     #[test]
-    fn test_internal() {
-        let json_struct: ResponseError = serde_json::from_str(INTERNAL_ERROR).unwrap();
+    fn test_request() {
+        let (address, proto) = serve("darkroom_test_grpc_request", create_user);
+        let prm = Params {
+            timeout: 5,
+            address,
+            proto: vec![proto.clone()],
+            proto_path: vec![proto.parent().unwrap().to_path_buf()],
+            header: Some("\"x-request-id: 1\"".to_string()),
+            ..Params::default()
+        };
+
+        let req: Request = serde_json::from_value(json!({
+            "uri": "darkroom.test.Users/Create",
+            "body": {"name": "James", "age": 40}
+        }))
+        .unwrap();
+        let response = request(prm.clone(), req).unwrap();
+        assert_eq!(0, response.status);
         assert_eq!(
-            ResponseError {
-                code:    13,
-                message: "input cannot be empty".to_owned(),
-            },
-            json_struct
+            Some(&json!("1")),
+            response.header.unwrap().get("x-request-id")
+        );
+        // 64 bit integers are returned as strings by protobuf JSON
+        assert_eq!(
+            Some(json!({"id": "007", "name": "James", "age": "40"})),
+            response.body
+        );
+
+        let req: Request = serde_json::from_value(json!({
+            "uri": "darkroom.test.Users.Create",
+            "body": {"name": ""}
+        }))
+        .unwrap();
+        let response = request(prm.clone(), req).unwrap();
+        assert_eq!(3, response.status);
+        assert_eq!(Some(json!("name is required")), response.body);
+        let etc = response.etc.unwrap();
+        assert_eq!(Some(&json!("1")), etc["trailers"].get("x-request-id"));
+        assert_eq!(
+            json!({"code": 3, "message": "name is required", "details": []}),
+            etc["error"]
+        );
+
+        let req: Request = serde_json::from_value(json!({
+            "uri": "darkroom.test.Users/Delete",
+            "body": {}
+        }))
+        .unwrap();
+        assert!(request(prm, req).is_err());
+    }
+
+    #[rstest(
+        method,
+        body,
+        expected,
+        // server stream
+        case(
+            "Repeat",
+            json!({"name": "James", "age": 2}),
+            json!([
+                {"id": "007", "name": "James", "age": "2"},
+                {"id": "007", "name": "James", "age": "2"}
+            ])
+        ),
+        // client stream
+        case(
+            "Last",
+            json!([{"name": "James", "age": 40}, {"name": "Jim", "age": 41}]),
+            json!({"id": "007", "name": "Jim", "age": "41"})
+        ),
+        // bidirectional stream
+        case(
+            "Import",
+            json!([{"name": "James", "age": 40}, {"name": "Jim", "age": 41}]),
+            json!([
+                {"id": "007", "name": "James", "age": "40"},
+                {"id": "007", "name": "Jim", "age": "41"}
+            ])
+        )
+    )]
+    fn test_request_streams(method: &str, body: Value, expected: Value) {
+        let (address, proto) = serve(
+            &format!("darkroom_test_grpc_stream_{}", method),
+            create_user,
         );
+        let prm = Params {
+            timeout: 5,
+            address,
+            proto: vec![proto.clone()],
+            proto_path: vec![proto.parent().unwrap().to_path_buf()],
+            ..Params::default()
+        };
+        let req: Request = serde_json::from_value(json!({
+            "uri": format!("darkroom.test.Users/{}", method),
+            "body": body
+        }))
+        .unwrap();
+        let response = request(prm, req).unwrap();
+        assert_eq!(0, response.status);
+        assert_eq!(Some(expected), response.body);
+    }
+
+    #[test]
+    fn test_descriptor_sources() {
+        let (address, proto) = serve("darkroom_test_grpc_descriptors", create_user);
+        let protoset = proto.with_extension("protoset");
+        fs::write(
+            &protoset,
+            protox::compile([&proto], [proto.parent().unwrap()])
+                .unwrap()
+                .encode_to_vec(),
+        )
+        .unwrap();
+        let req: Request = serde_json::from_value(json!({
+            "uri": "darkroom.test.Users/Create",
+            "body": {"name": "James", "age": 40}
+        }))
+        .unwrap();
+
+        // descriptors are requested through server reflection when no protos are given
+        for protoset in [vec![], vec![protoset]] {
+            let prm = Params {
+                timeout: 5,
+                address: address.clone(),
+                protoset,
+                ..Params::default()
+            };
+            let response = request(prm, req.clone()).unwrap();
+            assert_eq!(
+                Some(json!({"id": "007", "name": "James", "age": "40"})),
+                response.body
+            );
+        }
     }
 
     #[test]
-    fn test_auth() {
-        let json_struct: ResponseError =
-            serde_json::from_slice(&AUTH_ERROR.as_bytes().to_vec()).unwrap();
+    fn test_descriptor_pool_metadata() {
+        let (address, _) = serve("darkroom_test_grpc_reflection_metadata", create_user);
+        let req: Request = serde_json::from_value(json!({
+            "uri": "darkroom.test.Users/Create",
+            "body": {"name": "James", "age": 40}
+        }))
+        .unwrap();
+        let prm = |bearer: &str| Params {
+            timeout: 5,
+            address: address.clone(),
+            header: Some(format!("authorization: Bearer {}", bearer)),
+            ..Params::default()
+        };
+
+        // the reflection requests carry the metadata of the call
+        assert!(request(prm("expired"), req.clone()).is_err());
+        let response = request(prm("darkroom"), req).unwrap();
         assert_eq!(
-            ResponseError {
-                code:    16,
-                message: "rpc error: code = Unauthenticated desc = Empty JWT token".to_owned(),
+            Some(json!({"id": "007", "name": "James", "age": "40"})),
+            response.body
+        );
+    }
+
+    /// proxy starts an HTTP proxy tunnelling CONNECT requests, returning its url along with a
+    /// receiver of the request lines it was sent
+    fn proxy() -> (String, std::sync::mpsc::Receiver<String>) {
+        use std::{
+            io::{copy, Read, Write},
+            net::{TcpListener, TcpStream},
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            for client in listener.incoming() {
+                let mut client = client.unwrap();
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let mut head = Vec::new();
+                    let mut byte = [0u8];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        client.read_exact(&mut byte).unwrap();
+                        head.push(byte[0]);
+                    }
+                    let line = String::from_utf8(head)
+                        .unwrap()
+                        .lines()
+                        .next()
+                        .unwrap()
+                        .to_string();
+                    let target = line.split_whitespace().nth(1).unwrap().to_string();
+                    sender.send(line).unwrap();
+                    let mut server = TcpStream::connect(target).unwrap();
+                    client
+                        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                        .unwrap();
+                    let (mut client_read, mut server_write) =
+                        (client.try_clone().unwrap(), server.try_clone().unwrap());
+                    thread::spawn(move || copy(&mut client_read, &mut server_write));
+                    let _ = copy(&mut server, &mut client);
+                });
+            }
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn test_request_proxy() {
+        let (address, proto) = serve("darkroom_test_grpc_proxy", create_user);
+        let (proxy, requests) = proxy();
+        let prm = Params {
+            timeout: 5,
+            address: address.clone(),
+            proto: vec![proto.clone()],
+            proto_path: vec![proto.parent().unwrap().to_path_buf()],
+            proxy: Some(proxy),
+            ..Params::default()
+        };
+        let req: Request = serde_json::from_value(json!({
+            "uri": "darkroom.test.Users/Create",
+            "body": {"name": "James", "age": 40}
+        }))
+        .unwrap();
+        let response = request(prm, req).unwrap();
+        assert_eq!(0, response.status);
+        assert_eq!(
+            format!("CONNECT {} HTTP/1.1", address),
+            requests.recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_request_insecure() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let identity = Identity::from_pem(
+            cert.serialize_pem().unwrap(),
+            cert.serialize_private_key_pem(),
+        );
+        let (address, proto) = serve_tls(
+            "darkroom_test_grpc_insecure",
+            create_user,
+            Some(ServerTlsConfig::new().identity(identity)),
+        );
+        let port = address.rsplit(':').next().unwrap();
+        let req: Request = serde_json::from_value(json!({
+            "uri": "darkroom.test.Users/Create",
+            "body": {"name": "James", "age": 40}
+        }))
+        .unwrap();
+        let prm = |insecure| Params {
+            timeout: 5,
+            address: format!("localhost:{}", port),
+            tls: true,
+            tls_config: crate::params::TlsConfig {
+                insecure: Some(insecure),
+                ..Default::default()
             },
-            json_struct
+            proto: vec![proto.clone()],
+            proto_path: vec![proto.parent().unwrap().to_path_buf()],
+            ..Params::default()
+        };
+
+        // the self signed certificate is only accepted when it is not verified
+        assert!(request(prm(false), req.clone()).is_err());
+        let response = request(prm(true), req).unwrap();
+        assert_eq!(0, response.status);
+        assert_eq!(
+            Some(json!({"id": "007", "name": "James", "age": "40"})),
+            response.body
+        );
+    }
+
+    #[test]
+    fn test_build_metadata() {
+        let object_header =
+            r#"{"authorization": "Bearer jWt", "x-retry": 2, "x-trace-bin": "AAEC", "x-tag": ["a", "b"]}"#
+                .to_string();
+        let array_header = r#"["Authorization: Bearer jWt", "x-tag: a"]"#.to_string();
+        let string_header = "\"Authorization: Bearer jWt\"".to_string();
+        for (header, len) in [(object_header, 5), (array_header, 2), (string_header, 1)] {
+            let prm = Params {
+                header: Some(header),
+                ..Params::default()
+            };
+            let metadata = build_metadata(&prm).unwrap();
+            assert_eq!(len, metadata.len());
+            assert_eq!("Bearer jWt", metadata.get("authorization").unwrap());
+        }
+
+        let prm = Params {
+            header: Some(r#"{"x-trace-bin": "AAEC", "x-tag": ["a", "b"]}"#.to_string()),
+            ..Params::default()
+        };
+        let metadata = build_metadata(&prm).unwrap();
+        assert_eq!(
+            &[0u8, 1, 2][..],
+            metadata.get_bin("x-trace-bin").unwrap().to_bytes().unwrap()
+        );
+        assert_eq!(
+            json!({"x-trace-bin": "AAEC", "x-tag": ["a", "b"]}),
+            metadata_to_value(&metadata)
         );
     }
 }
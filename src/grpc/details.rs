@@ -0,0 +1,155 @@
+use lazy_static::lazy_static;
+use prost_reflect::{DescriptorPool, DynamicMessage, SerializeOptions};
+use protox::file::{ChainFileResolver, File, FileResolver, GoogleFileResolver};
+use serde_json::{json, Value};
+use tonic::Status;
+
+const STATUS_PROTO: &str = "google/rpc/status.proto";
+const ERROR_DETAILS_PROTO: &str = "google/rpc/error_details.proto";
+
+lazy_static! {
+    /// google.rpc.Status along with the standard error details such as BadRequest, ErrorInfo and
+    /// RetryInfo
+    static ref ERROR_DETAILS: DescriptorPool = {
+        let mut resolver = ChainFileResolver::new();
+        resolver.add(GoogleFileResolver::new());
+        resolver.add(RpcFileResolver);
+        protox::Compiler::with_file_resolver(resolver)
+            .include_imports(true)
+            .open_files([STATUS_PROTO, ERROR_DETAILS_PROTO])
+            .expect("google.rpc protos failed to compile")
+            .descriptor_pool()
+    };
+}
+
+/// RpcFileResolver provides the google.rpc protos bundled with darkroom
+struct RpcFileResolver;
+
+impl FileResolver for RpcFileResolver {
+    fn open_file(&self, name: &str) -> Result<File, protox::Error> {
+        match name {
+            STATUS_PROTO => File::from_source(name, include_str!("proto/google/rpc/status.proto")),
+            ERROR_DETAILS_PROTO => {
+                File::from_source(name, include_str!("proto/google/rpc/error_details.proto"))
+            }
+            _ => Err(protox::Error::file_not_found(name)),
+        }
+    }
+}
+
+/// status_to_value converts a returned gRPC status into a JSON object of its code, message and
+/// the details of the `google.rpc.Status` sent in the `grpc-status-details-bin` trailer. Details
+/// are decoded using the message types of the call's DescriptorPool or the standard error
+/// details, those of an unknown type keep their bytes as a base64 `"value"`
+pub fn status_to_value(status: &Status, pool: &DescriptorPool) -> Value {
+    let rpc_status = ERROR_DETAILS
+        .get_message_by_name("google.rpc.Status")
+        .expect("google.rpc.Status descriptor");
+    let details: Vec<Value> = match DynamicMessage::decode(rpc_status, status.details()) {
+        Ok(rpc_status) => rpc_status
+            .get_field_by_name("details")
+            .and_then(|d| {
+                d.as_list().map(|l| {
+                    l.iter()
+                        .filter_map(|v| v.as_message())
+                        .map(|any| detail_to_value(any, pool))
+                        .collect()
+                })
+            })
+            .unwrap_or_default(),
+        Err(_) => vec![],
+    };
+    json!({
+        "code": status.code() as i32,
+        "message": status.message(),
+        "details": details,
+    })
+}
+
+/// detail_to_value follows the JSON mapping of `google.protobuf.Any`, the decoded fields of the
+/// message are kept alongside its `"@type"`
+fn detail_to_value(any: &DynamicMessage, pool: &DescriptorPool) -> Value {
+    let type_url = any
+        .get_field_by_name("type_url")
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default();
+    let bytes = any
+        .get_field_by_name("value")
+        .and_then(|v| v.as_bytes().cloned())
+        .unwrap_or_default();
+    let name = type_url.rsplit('/').next().unwrap_or_default();
+
+    let decoded = pool
+        .get_message_by_name(name)
+        .or_else(|| ERROR_DETAILS.get_message_by_name(name))
+        .and_then(|m| DynamicMessage::decode(m, bytes.as_ref()).ok())
+        .and_then(|m| {
+            m.serialize_with_options(serde_json::value::Serializer, &SerializeOptions::new())
+                .ok()
+        });
+    match decoded {
+        Some(Value::Object(mut map)) => {
+            map.insert("@type".to_string(), Value::String(type_url));
+            Value::Object(map)
+        }
+        _ => json!({"@type": type_url, "value": base64::encode(&bytes)}),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use tonic::Code;
+
+    #[test]
+    fn test_status_to_value() {
+        let rpc_status = DynamicMessage::deserialize(
+            ERROR_DETAILS
+                .get_message_by_name("google.rpc.Status")
+                .unwrap(),
+            json!({
+                "code": 3,
+                "message": "name is required",
+                "details": [
+                    {
+                        "@type": "type.googleapis.com/google.rpc.BadRequest",
+                        "fieldViolations": [{"field": "name", "description": "must not be empty"}]
+                    },
+                    {
+                        "@type": "type.googleapis.com/google.rpc.RetryInfo",
+                        "retryDelay": "1.500s"
+                    }
+                ]
+            }),
+        )
+        .unwrap();
+        let status = Status::with_details(
+            Code::InvalidArgument,
+            "name is required",
+            rpc_status.encode_to_vec().into(),
+        );
+
+        assert_eq!(
+            json!({
+                "code": 3,
+                "message": "name is required",
+                "details": [
+                    {
+                        "@type": "type.googleapis.com/google.rpc.BadRequest",
+                        "fieldViolations": [{"field": "name", "description": "must not be empty"}]
+                    },
+                    {
+                        "@type": "type.googleapis.com/google.rpc.RetryInfo",
+                        "retryDelay": "1.500s"
+                    }
+                ]
+            }),
+            status_to_value(&status, &DescriptorPool::new())
+        );
+        assert_eq!(
+            json!({"code": 5, "message": "missing", "details": []}),
+            status_to_value(&Status::not_found("missing"), &DescriptorPool::new())
+        );
+    }
+}
@@ -0,0 +1,395 @@
+use crate::params::{iter_path_args, Params};
+use anyhow::{anyhow, Context, Error};
+use filmreel::{frame::Request, response::Response};
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{ffi::OsStr, path::PathBuf, process::Command};
+
+/// Checks to see if grpcurl is in the system path
+pub fn validate_grpcurl() -> Result<(), Error> {
+    lazy_static! {
+        static ref GRPCURL: which::Result<PathBuf> = which::which("grpcurl");
+    }
+    if !GRPCURL.is_ok() {
+        return Err(anyhow!("`grpcurl` was not found! Check your PATH!"));
+    }
+    Ok(())
+}
+
+/// request parses a Frame Request and a Params object to send a gRPC payload using `grpcurl`
+/// the command line tool
+pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
+    validate_grpcurl().context("grpcurl request failure")?;
+
+    let mut flags: Vec<&OsStr> = vec![OsStr::new("-format-error")];
+
+    // any TLS option implies a TLS connection
+    if !prm.tls && !prm.tls_config.is_set() {
+        flags.push(OsStr::new("-plaintext"));
+    }
+
+    if prm.tls_config.is_insecure() {
+        flags.push(OsStr::new("-insecure"));
+    }
+
+    for (flag, path) in [
+        ("-cacert", &prm.tls_config.cacert),
+        ("-cert", &prm.tls_config.cert),
+        ("-key", &prm.tls_config.key),
+    ]
+    .iter()
+    {
+        if let Some(p) = path {
+            flags.push(OsStr::new(*flag));
+            flags.push(p.as_os_str());
+        }
+    }
+
+    // prepend "-import-path" to every protos PathBuf provided
+    flags.extend(iter_path_args(
+        OsStr::new("-import-path"),
+        prm.proto_path.iter().map(OsStr::new),
+    ));
+
+    // prepend "-proto" to every protos PathBuf provided
+    flags.extend(iter_path_args(
+        OsStr::new("-proto"),
+        prm.proto.iter().map(OsStr::new),
+    ));
+
+    // prepend "-protoset" to every protoset PathBuf provided, grpcurl falls back to server
+    // reflection when neither protos nor protosets are given
+    flags.extend(iter_path_args(
+        OsStr::new("-protoset"),
+        prm.protoset.iter().map(OsStr::new),
+    ));
+
+    // every metadata entry is passed with its own "-H" flag, grpcurl base64 decodes "-bin" values
+    let headers: Vec<String> = match &prm.header {
+        Some(h) => super::metadata_entries(h)?
+            .into_iter()
+            .map(|(k, v)| format!("{}: {}", k, v))
+            .collect(),
+        None => vec![],
+    };
+    flags.extend(iter_path_args(
+        OsStr::new("-H"),
+        headers.iter().map(OsStr::new),
+    ));
+
+    let bearer = match &prm.oauth {
+        Some(oauth) => {
+            let client = prm.session.client(&prm.tls_config, prm.proxy.as_deref())?;
+            Some(format!("authorization: Bearer {}", oauth.bearer(&client)?))
+        }
+        None => None,
+    };
+
+    if let Some(b) = &bearer {
+        flags.push(OsStr::new("-H"));
+        flags.push(OsStr::new(b));
+    }
+
+    let grpcurl = || {
+        let mut grpcurl = Command::new("grpcurl");
+        // grpcurl reads its proxy from the environment
+        if let Some(proxy) = &prm.proxy {
+            grpcurl.env("HTTPS_PROXY", proxy).env("HTTP_PROXY", proxy);
+        }
+        grpcurl
+            .args(&flags)
+            .arg("-connect-timeout")
+            .arg(format!("{:.1}", prm.timeout as f32));
+        grpcurl
+    };
+
+    // grpcurl reads every JSON message of -d in order for client streams
+    let payload = match req.to_val_payload()? {
+        Some(serde_json::Value::Array(messages)) => messages
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+        _ => req.to_payload()?,
+    };
+
+    prm.stopwatch.start();
+    let req_cmd = grpcurl()
+        .arg("-d")
+        .arg(payload)
+        .arg(&prm.address)
+        .arg(req.get_uri())
+        .output()
+        .context("failed to execute grpcurl process")?;
+    prm.stopwatch.stop();
+
+    let response = match req_cmd.status.code() {
+        Some(0) => Response {
+            body:       Some(stdout_body(&req_cmd.stdout, || {
+                server_streaming(grpcurl(), &prm, &req.get_uri())
+            })?),
+            header:     None,
+            errors:     None,
+            status:     0,
+            etc:        Some(json!({})),
+            validation: None,
+            max_ms:     None,
+        },
+        Some(_) => {
+            let err: ResponseError = serde_json::from_slice(&req_cmd.stderr).map_err(|_| {
+                // if we fail to map to a serde struct, stringingfy stderr bytes and cast to anyhow error
+                String::from_utf8(req_cmd.stderr)
+                    .map_err(Error::from)
+                    .map(|v| anyhow!(v))
+                    .context("grpcurl error")
+                    .unwrap_or_else(|e| e)
+            })?;
+            // create frame response from deserialized grpcurl error
+            Response {
+                body:       Some(serde_json::Value::String(err.message.clone())),
+                header:     None,
+                errors:     None,
+                status:     err.code,
+                etc:        Some(json!({ "error": err })),
+                validation: None,
+                max_ms:     None,
+            }
+        }
+        None => return Err(anyhow!("grpcurl response code was <None>")),
+    };
+    Ok(response)
+}
+
+/// stdout_body reads the messages printed by grpcurl, the messages of a server stream are
+/// returned as an array as they are by the native client, however many were received. Only a
+/// single message needs server_streaming to tell a unary reply from a stream of one message
+fn stdout_body<F>(stdout: &[u8], server_streaming: F) -> Result<serde_json::Value, Error>
+where
+    F: FnOnce() -> bool,
+{
+    let mut messages = serde_json::Deserializer::from_slice(stdout)
+        .into_iter::<serde_json::Value>()
+        .collect::<Result<Vec<_>, _>>()?;
+    match messages.len() {
+        1 if !server_streaming() => Ok(messages.remove(0)),
+        _ => Ok(serde_json::Value::Array(messages)),
+    }
+}
+
+/// server_streaming looks the method of the uri up in the protos or protosets of the call, or runs
+/// `grpcurl describe` on it when the descriptors come from server reflection. A method that cannot
+/// be described is taken to be unary
+fn server_streaming(grpcurl: Command, prm: &Params, uri: &str) -> bool {
+    let described = match super::file_pool(prm) {
+        Ok(Some(pool)) => super::split_uri(uri)
+            .and_then(|(service, method)| super::method_descriptor(&pool, service, method))
+            .map(|method| method.is_server_streaming()),
+        Ok(None) => describe(grpcurl, &prm.address, uri),
+        Err(e) => Err(e),
+    };
+    described.unwrap_or_else(|e| {
+        warn!("unable to describe {}, taking it as unary: {:#}", uri, e);
+        false
+    })
+}
+
+/// describe runs `grpcurl describe` on the method of the uri, telling whether it is a server
+/// stream
+fn describe(mut grpcurl: Command, address: &str, uri: &str) -> Result<bool, Error> {
+    let output = grpcurl
+        .arg(address)
+        .arg("describe")
+        .arg(uri.replace('/', "."))
+        .output()
+        .context("failed to execute grpcurl describe process")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "grpcurl describe failure: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    describes_server_stream(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| anyhow!("grpcurl describe did not describe a method"))
+}
+
+/// describes_server_stream reads the rpc declaration printed by `grpcurl describe`:
+/// `rpc Repeat ( .users.CreateRequest ) returns ( stream .users.User );`
+fn describes_server_stream(describe: &str) -> Option<bool> {
+    let rpc = describe
+        .lines()
+        .map(str::trim_start)
+        .find(|l| l.starts_with("rpc "))?;
+    let (_, returns) = rpc.split_once(" returns ")?;
+    let returns = returns.trim_start().strip_prefix('(')?.trim_start();
+    Some(returns.starts_with("stream "))
+}
+
+/// ResponseError is the status printed by grpcurl with `-format-error`, details are decoded by
+/// grpcurl using the descriptors of the call
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct ResponseError {
+    code:    u32,
+    message: String,
+    #[serde(default)]
+    details: Vec<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod serde_tests {
+    use super::*;
+    use serde_json;
+
+    const INTERNAL_ERROR: &str = r#"{
+  "code": 13,
+  "message": "input cannot be empty"
+}"#;
+    const AUTH_ERROR: &str = r#"{
+  "code": 16,
+  "message": "rpc error: code = Unauthenticated desc = Empty JWT token"
+}"#;
+
+    #[test]
+    fn test_internal() {
+        let json_struct: ResponseError = serde_json::from_str(INTERNAL_ERROR).unwrap();
+        assert_eq!(
+            ResponseError {
+                code:    13,
+                message: "input cannot be empty".to_owned(),
+                details: vec![],
+            },
+            json_struct
+        );
+    }
+
+    const DETAILS_ERROR: &str = r#"{
+  "code": 3,
+  "message": "name is required",
+  "details": [
+    {
+      "@type": "type.googleapis.com/google.rpc.BadRequest",
+      "fieldViolations": [
+        {
+          "field": "name",
+          "description": "must not be empty"
+        }
+      ]
+    }
+  ]
+}"#;
+
+    #[test]
+    fn test_details() {
+        let json_struct: ResponseError = serde_json::from_str(DETAILS_ERROR).unwrap();
+        assert_eq!(
+            ResponseError {
+                code:    3,
+                message: "name is required".to_owned(),
+                details: vec![json!({
+                    "@type": "type.googleapis.com/google.rpc.BadRequest",
+                    "fieldViolations": [{"field": "name", "description": "must not be empty"}]
+                })],
+            },
+            json_struct
+        );
+    }
+
+    #[test]
+    fn test_stdout_body() {
+        assert_eq!(
+            json!({"id": 1}),
+            stdout_body(b"{\n  \"id\": 1\n}\n", || false).unwrap()
+        );
+        // a server stream yielding a single message is still returned as an array
+        assert_eq!(
+            json!([{"id": 1}]),
+            stdout_body(b"{\n  \"id\": 1\n}\n", || true).unwrap()
+        );
+        // several messages can only be a stream, so the method is never described
+        assert_eq!(
+            json!([{"id": 1}, {"id": 2}]),
+            stdout_body(b"{\n  \"id\": 1\n}\n{\n  \"id\": 2\n}\n", || {
+                unreachable!()
+            })
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_server_streaming() {
+        let dir = std::env::temp_dir().join("darkroom_test_grpcurl_streaming");
+        std::fs::create_dir_all(&dir).unwrap();
+        let proto = dir.join("users.proto");
+        std::fs::write(
+            &proto,
+            "syntax = \"proto3\";\npackage darkroom.test;\nmessage User {}\n\
+             service Users {\n  rpc Get(User) returns (User);\n  \
+             rpc Repeat(User) returns (stream User);\n}\n",
+        )
+        .unwrap();
+        // describing through a missing grpcurl fails, only the protos can tell a stream
+        let grpcurl = || Command::new("darkroom-missing-grpcurl");
+        let prm = Params {
+            proto: vec![proto],
+            proto_path: vec![dir.clone()],
+            ..Params::default()
+        };
+        assert!(server_streaming(
+            grpcurl(),
+            &prm,
+            "darkroom.test.Users/Repeat"
+        ));
+        assert!(!server_streaming(
+            grpcurl(),
+            &prm,
+            "darkroom.test.Users/Get"
+        ));
+        // a method that cannot be described is taken to be unary
+        assert!(!server_streaming(
+            grpcurl(),
+            &Params::default(),
+            "darkroom.test.Users/Repeat"
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_describes_server_stream() {
+        let describe = |rpc: &str| {
+            describes_server_stream(&format!(
+                "darkroom.test.Users.Repeat is a method:\n{}\n",
+                rpc
+            ))
+        };
+        assert_eq!(
+            Some(true),
+            describe("rpc Repeat ( .darkroom.test.CreateRequest ) returns ( stream .darkroom.test.User );")
+        );
+        assert_eq!(
+            Some(true),
+            describe("rpc Import ( stream .darkroom.test.CreateRequest ) returns ( stream .darkroom.test.User );")
+        );
+        assert_eq!(
+            Some(false),
+            describe(
+                "rpc Last ( stream .darkroom.test.CreateRequest ) returns ( .darkroom.test.User );"
+            )
+        );
+        assert_eq!(None, describe("message User {\n}"));
+    }
+
+    #[test]
+    fn test_auth() {
+        let json_struct: ResponseError =
+            serde_json::from_slice(&AUTH_ERROR.as_bytes().to_vec()).unwrap();
+        assert_eq!(
+            ResponseError {
+                code:    16,
+                message: "rpc error: code = Unauthenticated desc = Empty JWT token".to_owned(),
+                details: vec![],
+            },
+            json_struct
+        );
+    }
+}
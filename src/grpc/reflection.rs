@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Context, Error};
+use http::uri::PathAndQuery;
+use prost::Message;
+use prost_reflect::{prost_types::FileDescriptorProto, DescriptorPool};
+use std::collections::HashMap;
+use tonic::{
+    codec::ProstCodec, codegen::tokio_stream, metadata::MetadataMap, transport::Channel, Code,
+    Status, Streaming,
+};
+
+const REFLECTION_V1: &str = "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo";
+const REFLECTION_V1ALPHA: &str = "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo";
+
+/// descriptor_pool requests the file containing a service through the gRPC server reflection API
+/// along with every file it depends on, sending the metadata of the call with every request. The
+/// v1alpha API is used when the server lacks v1
+pub async fn descriptor_pool(
+    channel: Channel,
+    service: &str,
+    metadata: MetadataMap,
+) -> Result<DescriptorPool, Error> {
+    let mut reflection = Reflection {
+        client: tonic::client::Grpc::new(channel),
+        path: PathAndQuery::from_static(REFLECTION_V1),
+        metadata,
+    };
+    let request = MessageRequest::FileContainingSymbol(service.to_string());
+    let mut pending = match reflection.files(request.clone()).await {
+        Err(e) if matches!(e.downcast_ref::<Status>(), Some(s) if s.code() == Code::Unimplemented) =>
+        {
+            reflection.path = PathAndQuery::from_static(REFLECTION_V1ALPHA);
+            reflection.files(request).await
+        }
+        files => files,
+    }?;
+
+    let mut files: HashMap<String, FileDescriptorProto> = HashMap::new();
+    while let Some(file) = pending.pop() {
+        if files.contains_key(file.name()) {
+            continue;
+        }
+        for dependency in &file.dependency {
+            if !files.contains_key(dependency) && !pending.iter().any(|f| f.name() == dependency) {
+                pending.extend(
+                    reflection
+                        .files(MessageRequest::FileByFilename(dependency.clone()))
+                        .await
+                        .context(format!("server reflection of {}", dependency))?,
+                );
+            }
+        }
+        files.insert(file.name().to_string(), file);
+    }
+
+    let mut pool = DescriptorPool::new();
+    pool.add_file_descriptor_protos(files.into_values())?;
+    Ok(pool)
+}
+
+struct Reflection {
+    client:   tonic::client::Grpc<Channel>,
+    path:     PathAndQuery,
+    metadata: MetadataMap,
+}
+
+impl Reflection {
+    /// files sends a single reflection request returning the file descriptors of the response
+    async fn files(&mut self, request: MessageRequest) -> Result<Vec<FileDescriptorProto>, Error> {
+        self.client.ready().await?;
+        let request = ServerReflectionRequest {
+            host:            String::new(),
+            message_request: Some(request),
+        };
+        let mut request = tonic::Request::new(tokio_stream::iter(vec![request]));
+        *request.metadata_mut() = self.metadata.clone();
+        let mut stream: Streaming<ServerReflectionResponse> = self
+            .client
+            .streaming(request, self.path.clone(), ProstCodec::default())
+            .await?
+            .into_inner();
+
+        match stream.message().await?.and_then(|r| r.message_response) {
+            Some(MessageResponse::FileDescriptorResponse(r)) => r
+                .file_descriptor_proto
+                .iter()
+                .map(|b| FileDescriptorProto::decode(b.as_slice()).map_err(Error::from))
+                .collect(),
+            Some(MessageResponse::ErrorResponse(e)) => {
+                Err(Status::new(Code::from(e.error_code), e.error_message).into())
+            }
+            None => Err(anyhow!("server reflection returned no file descriptors")),
+        }
+    }
+}
+
+// messages of grpc/reflection/v1/reflection.proto used by darkroom, the v1alpha messages share
+// the same field numbers
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ServerReflectionRequest {
+    #[prost(string, tag = "1")]
+    pub host:            String,
+    #[prost(oneof = "MessageRequest", tags = "3, 4")]
+    pub message_request: Option<MessageRequest>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum MessageRequest {
+    #[prost(string, tag = "3")]
+    FileByFilename(String),
+    #[prost(string, tag = "4")]
+    FileContainingSymbol(String),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ServerReflectionResponse {
+    #[prost(oneof = "MessageResponse", tags = "4, 7")]
+    pub message_response: Option<MessageResponse>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum MessageResponse {
+    #[prost(message, tag = "4")]
+    FileDescriptorResponse(FileDescriptorResponse),
+    #[prost(message, tag = "7")]
+    ErrorResponse(ErrorResponse),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FileDescriptorResponse {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub file_descriptor_proto: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ErrorResponse {
+    #[prost(int32, tag = "1")]
+    pub error_code:    i32,
+    #[prost(string, tag = "2")]
+    pub error_message: String,
+}
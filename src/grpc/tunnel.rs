@@ -0,0 +1,203 @@
+use crate::params::TlsConfig;
+use anyhow::{anyhow, Context, Error};
+use http::Uri;
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, PrivateKey, ServerName,
+};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    future::Future,
+    io::{self, BufReader},
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::SystemTime,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_rustls::TlsConnector;
+use tonic::codegen::Service;
+use url::Url;
+
+/// Io is a connection opened by a Tunnel
+pub(super) trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// Tunnel opens the connections of a native gRPC channel through an HTTP `CONNECT` proxy and,
+/// when the server certificate is not verified, negotiates TLS itself since tonic always verifies
+#[derive(Clone)]
+pub(super) struct Tunnel {
+    proxy: Option<Url>,
+    tls:   Option<(Arc<ClientConfig>, ServerName)>,
+}
+
+impl Tunnel {
+    /// new returns a Tunnel through the given proxy, insecure TLS is negotiated with the domain of
+    /// the address when the TlsConfig skips verification
+    pub(super) fn new(
+        proxy: Option<&str>,
+        tls_config: &TlsConfig,
+        domain: &str,
+    ) -> Result<Self, Error> {
+        let proxy = match proxy {
+            Some(p) => {
+                let url = Url::parse(p).context(format!("proxy: {}", p))?;
+                if url.scheme() != "http" {
+                    return Err(anyhow!(
+                        "gRPC frames can only be sent through an http:// proxy, got {}",
+                        p
+                    ));
+                }
+                Some(url)
+            }
+            None => None,
+        };
+        let tls = match tls_config.is_insecure() {
+            true => {
+                let name = ServerName::try_from(unbracket(domain))
+                    .context(format!("invalid TLS server name: {}", domain))?;
+                Some((Arc::new(insecure_config(tls_config)?), name))
+            }
+            false => None,
+        };
+        Ok(Self { proxy, tls })
+    }
+
+    async fn connect(self, uri: Uri) -> io::Result<Box<dyn Io>> {
+        let host = uri
+            .host()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "address without a host"))?;
+        let default_port = match uri.scheme_str() {
+            Some("https") => 443,
+            _ => 80,
+        };
+        let port = uri.port_u16().unwrap_or(default_port);
+
+        let stream = match &self.proxy {
+            Some(proxy) => connect_proxy(proxy, host, port).await?,
+            None => TcpStream::connect((unbracket(host), port)).await?,
+        };
+        stream.set_nodelay(true)?;
+        match self.tls {
+            Some((config, name)) => Ok(Box::new(
+                TlsConnector::from(config).connect(name, stream).await?,
+            )),
+            None => Ok(Box::new(stream)),
+        }
+    }
+}
+
+impl Service<Uri> for Tunnel {
+    type Response = Box<dyn Io>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Box<dyn Io>>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(self.clone().connect(uri))
+    }
+}
+
+/// unbracket strips the brackets surrounding an IPv6 host
+fn unbracket(host: &str) -> &str {
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// connect_proxy asks an HTTP proxy to open a tunnel to the given host
+async fn connect_proxy(proxy: &Url, host: &str, port: u16) -> io::Result<TcpStream> {
+    let proxy_err = |msg: String| io::Error::other(msg);
+    let proxy_host = proxy
+        .host_str()
+        .ok_or_else(|| proxy_err(format!("proxy without a host: {}", proxy)))?;
+    let proxy_port = proxy.port_or_known_default().unwrap_or(80);
+    let mut stream = TcpStream::connect((unbracket(proxy_host), proxy_port)).await?;
+
+    let authority = format!("{}:{}", host, port);
+    let mut head = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if !proxy.username().is_empty() {
+        let credentials = format!("{}:{}", proxy.username(), proxy.password().unwrap_or(""));
+        head.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            base64::encode(credentials)
+        ));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+
+    // the reply is read a byte at a time so that no tunnelled bytes are consumed
+    let mut reply = Vec::new();
+    while !reply.ends_with(b"\r\n\r\n") {
+        if reply.len() > 8192 {
+            return Err(proxy_err("proxy CONNECT reply is too long".to_string()));
+        }
+        reply.push(stream.read_u8().await?);
+    }
+    let reply = String::from_utf8_lossy(&reply);
+    let status_line = reply.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(stream),
+        _ => Err(proxy_err(format!(
+            "proxy refused to CONNECT to {}: {}",
+            authority, status_line
+        ))),
+    }
+}
+
+/// insecure_config negotiates HTTP/2 over TLS without verifying the server certificate,
+/// presenting the client certificate when one is given
+fn insecure_config(tls_config: &TlsConfig) -> Result<ClientConfig, Error> {
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(SkipVerification));
+    let mut config = match (&tls_config.cert, &tls_config.key) {
+        (Some(cert), Some(key)) => {
+            let certs = rustls_pemfile::certs(&mut BufReader::new(
+                File::open(cert).context(format!("cert: {}", cert.display()))?,
+            ))?;
+            let key = rustls_pemfile::read_all(&mut BufReader::new(
+                File::open(key).context(format!("key: {}", key.display()))?,
+            ))?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::PKCS8Key(k)
+                | rustls_pemfile::Item::RSAKey(k)
+                | rustls_pemfile::Item::ECKey(k) => Some(k),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("key: no private key found in {}", key.display()))?;
+            builder.with_client_auth_cert(
+                certs.into_iter().map(Certificate).collect(),
+                PrivateKey(key),
+            )?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err(anyhow!("a client cert and key must be provided together")),
+    };
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(config)
+}
+
+/// SkipVerification accepts any server certificate, as `--insecure` does for HTTP frames
+struct SkipVerification;
+
+impl ServerCertVerifier for SkipVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
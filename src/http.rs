@@ -1,23 +1,156 @@
-use crate::params::Params;
+use crate::{
+    params::{Params, Redirect, ResponseFormat, TlsConfig},
+    websocket::Connection,
+};
 use anyhow::{anyhow, Context, Error};
+use colored::*;
 use filmreel::{frame::Request, response::Response};
-use http::header::HeaderMap;
-use log::warn;
-use reqwest::{blocking::*, Method};
-use serde_json::{json, Value};
-use std::{collections::HashMap, convert::TryFrom, time::Duration};
+use http::header::{self, HeaderMap};
+use log::info;
+use reqwest::{
+    blocking::{
+        self,
+        multipart::{Form, Part},
+        *,
+    },
+    redirect, Certificate, Identity, Method, Proxy, StatusCode,
+};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use url::Url;
 
-/// build_request parses a Frame Request and a Params object to send a HTTP payload using reqwest
-pub fn build_request(prm: &Params, req: Request) -> Result<RequestBuilder, Error> {
+/// Session holds the HTTP state that persists across every take of a single `take` or `record`
+/// run, such as the cookies set by earlier frames, the pooled clients sending every request and
+/// the WebSocket connections kept open between frames
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    cookies: Arc<CookieStoreMutex>,
+    clients: Arc<Mutex<HashMap<ClientKey, Client>>>,
+    sockets: Arc<Mutex<HashMap<String, Connection>>>,
+    http2:   bool,
+}
+
+/// ClientKey identifies the connection options a pooled Client was built with
+type ClientKey = (TlsConfig, Option<String>);
+
+impl Session {
+    /// new creates an empty session, http2 forces HTTP/2 with prior knowledge rather than
+    /// negotiating the protocol version through ALPN
+    pub fn new(http2: bool) -> Self {
+        Self {
+            http2,
+            ..Self::default()
+        }
+    }
+
+    /// client lazily builds the Client shared by every request of the session using the same
+    /// TLS options and proxy so that open connections are kept alive and reused between frames
+    pub fn client(&self, tls_config: &TlsConfig, proxy: Option<&str>) -> Result<Client, Error> {
+        let key = (tls_config.clone(), proxy.map(String::from));
+        let mut clients = self.clients.lock().expect("client pool lock poisoned");
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let mut builder = Client::builder()
+            // timeouts are set per request from Params.timeout
+            .timeout(None)
+            // redirects are followed in `send` using the policy of each frame
+            .redirect(redirect::Policy::none())
+            .cookie_provider(self.cookies.clone())
+            .danger_accept_invalid_certs(tls_config.is_insecure());
+        if self.http2 {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(cacert) = &tls_config.cacert {
+            let pem = fs::read(cacert).context(format!("cacert: {}", cacert.display()))?;
+            for cert in Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        match (&tls_config.cert, &tls_config.key) {
+            (Some(cert), Some(key)) => {
+                let cert = fs::read(cert).context(format!("cert: {}", cert.display()))?;
+                let key = fs::read(key).context(format!("key: {}", key.display()))?;
+                builder = builder.identity(Identity::from_pkcs8_pem(&cert, &key)?);
+            }
+            (None, None) => (),
+            _ => return Err(anyhow!("a client cert and key must be provided together")),
+        }
+        if let Some(p) = proxy {
+            builder = builder.proxy(Proxy::all(p).context(format!("proxy: {}", p))?);
+        }
+
+        let client = builder.build()?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// cookies_for returns the name and value of every cookie that would be sent to the given url
+    pub fn cookies_for(&self, url: &Url) -> Map<String, Value> {
+        let store = self.cookies.lock().expect("cookie store lock poisoned");
+        store
+            .get_request_values(url)
+            .map(|(name, value)| (name.to_string(), Value::String(value.to_string())))
+            .collect()
+    }
+
+    /// take_socket removes the WebSocket connection an earlier frame kept open to the given url
+    pub fn take_socket(&self, url: &Url) -> Option<Connection> {
+        let mut sockets = self.sockets.lock().expect("socket pool lock poisoned");
+        sockets.remove(url.as_str())
+    }
+
+    /// keep_socket holds a WebSocket connection open for the following frames of the session
+    pub fn keep_socket(&self, url: &Url, connection: Connection) {
+        let mut sockets = self.sockets.lock().expect("socket pool lock poisoned");
+        sockets.insert(url.to_string(), connection);
+    }
+
+    /// to_cookie_value serializes every unexpired cookie in the session
+    pub fn to_cookie_value(&self) -> Value {
+        let store = self.cookies.lock().expect("cookie store lock poisoned");
+        store
+            .iter_unexpired()
+            .map(|c| {
+                json!({
+                    "name": c.name(),
+                    "value": c.value(),
+                    "domain": c.domain.as_cow(),
+                    "path": c.path.as_ref(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Sessions are equal only when they share the same underlying state
+impl PartialEq for Session {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cookies, &other.cookies)
+            && Arc::ptr_eq(&self.clients, &other.clients)
+            && Arc::ptr_eq(&self.sockets, &other.sockets)
+            && self.http2 == other.http2
+    }
+}
+
+/// build_request parses a Frame Request and a Params object to build a HTTP request using reqwest,
+/// the request is authenticated last so that signatures cover the final request
+pub fn build_request(prm: &Params, req: Request) -> Result<blocking::Request, Error> {
     let method: Method;
     let endpoint: Url;
 
-    let timeout = match prm.timeout {
-        0 => None,
-        _ => Some(Duration::from_secs(prm.timeout)),
-    };
-
     match &req
         .get_uri()
         .splitn(2, ' ')
@@ -41,12 +174,18 @@ such as 'data:' mailto: URLs, and localhost without a leading http:// or https:/
         }
     };
 
-    let mut builder = Client::builder()
-        .timeout(timeout)
-        .build()?
-        .request(method, endpoint);
+    let mut builder = prm
+        .session
+        .client(&prm.tls_config, prm.proxy.as_deref())?
+        .request(method, endpoint)
+        // payloads are decompressed in `request` so that the Content-Encoding remains visible
+        .header(header::ACCEPT_ENCODING, "gzip, deflate, br");
+    if prm.timeout != 0 {
+        builder = builder.timeout(Duration::from_secs(prm.timeout));
+    }
     if let Some(b) = req.to_val_payload()? {
-        builder = builder.body(b.to_string());
+        let etc = req.get_etc();
+        builder = builder.body(body_bytes(b, etc.as_ref().and_then(|e| e.get("raw")))?);
     }
 
     if let Some(etc) = req.get_etc() {
@@ -61,16 +200,110 @@ such as 'data:' mailto: URLs, and localhost without a leading http:// or https:/
             Some(Value::Null) | None => (),
             _ => return Err(anyhow!("request[\"query\"] must be a key value map")),
         }
+
+        match etc.get("content_type") {
+            Some(Value::String(c)) => builder = builder.header(header::CONTENT_TYPE, c),
+            Some(Value::Null) | None => (),
+            _ => return Err(anyhow!("request[\"content_type\"] must be a string")),
+        }
+
+        match etc.get("multipart") {
+            Some(Value::Object(m)) => {
+                if req.to_val_payload()?.is_some() {
+                    return Err(anyhow!(
+                        "request[\"multipart\"] cannot be sent alongside a request body"
+                    ));
+                }
+                builder = builder.multipart(build_multipart(m, prm.frame_dir)?)
+            }
+            Some(Value::Null) | None => (),
+            _ => return Err(anyhow!("request[\"multipart\"] must be a key value map")),
+        }
     }
 
     if let Some(h) = &prm.header {
         builder = builder.headers(build_header(&h)?);
     }
-    Ok(builder)
+
+    let mut request = builder.build()?;
+    if let Some(auth) = &prm.auth {
+        auth.apply(&mut request)?;
+    }
+    if let Some(oauth) = &prm.oauth {
+        let client = prm.session.client(&prm.tls_config, prm.proxy.as_deref())?;
+        let bearer = format!("Bearer {}", oauth.bearer(&client)?);
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse()?);
+    }
+    Ok(request)
+}
+
+/// body_bytes serializes the request body as JSON unless a `"raw"` option is present:
+/// - `"raw": true` sends a string body verbatim
+/// - `"raw": "base64"` sends the bytes decoded from a base64 string body
+fn body_bytes(body: Value, raw: Option<&Value>) -> Result<Vec<u8>, Error> {
+    match (raw, body) {
+        (None, b) | (Some(Value::Null), b) | (Some(Value::Bool(false)), b) => {
+            Ok(b.to_string().into_bytes())
+        }
+        (Some(Value::Bool(true)), Value::String(b)) => Ok(b.into_bytes()),
+        (Some(Value::Bool(true)), b) => Ok(b.to_string().into_bytes()),
+        (Some(Value::String(r)), Value::String(b)) if r == "base64" => {
+            base64::decode(b.trim()).context("request[\"raw\"] base64 body decode failure")
+        }
+        (Some(Value::String(r)), _) if r == "base64" => Err(anyhow!(
+            "request[\"raw\"] base64 requires a string request body"
+        )),
+        _ => Err(anyhow!("request[\"raw\"] must be a boolean or \"base64\"")),
+    }
+}
+
+/// FilePart is a multipart field that is read from a file relative to the frame
+#[derive(Debug, Deserialize, PartialEq)]
+struct FilePart {
+    file:         PathBuf,
+    filename:     Option<String>,
+    content_type: Option<String>,
+}
+
+/// build_multipart constructs a multipart/form-data form from the `"multipart"` request map,
+/// objects containing a `"file"` key become file parts while any other value is sent as text
+fn build_multipart(map: &Map<String, Value>, frame_dir: Option<&Path>) -> Result<Form, Error> {
+    let mut form = Form::new();
+    for (name, value) in map.iter() {
+        let part = match value {
+            Value::Object(o) if o.contains_key("file") => {
+                let file_part: FilePart = serde_json::from_value(value.clone())
+                    .context(format!("request[\"multipart\"][\"{}\"]", name))?;
+                let path = match frame_dir {
+                    Some(dir) => dir.join(&file_part.file),
+                    None => file_part.file.clone(),
+                };
+                let filename = file_part
+                    .filename
+                    .or_else(|| path.file_name().map(|f| f.to_string_lossy().into_owned()));
+                let mut part = Part::bytes(
+                    fs::read(&path).context(format!("multipart file: {}", path.display()))?,
+                );
+                if let Some(f) = filename {
+                    part = part.file_name(f);
+                }
+                if let Some(c) = file_part.content_type {
+                    part = part.mime_str(&c)?;
+                }
+                part
+            }
+            Value::String(s) => Part::text(s.clone()),
+            v => Part::text(v.to_string()),
+        };
+        form = form.part(name.clone(), part);
+    }
+    Ok(form)
 }
 
 /// build_header constructs a header map from the header arg passed in from a ::Take or ::Record struct
-fn build_header(header: &str) -> Result<HeaderMap, Error> {
+pub fn build_header(header: &str) -> Result<HeaderMap, Error> {
     let map: HashMap<String, String> = serde_json::from_str(header)?;
     match HeaderMap::try_from(&map) {
         Ok(m) => Ok(m),
@@ -78,29 +311,238 @@ fn build_header(header: &str) -> Result<HeaderMap, Error> {
     }
 }
 
+/// decompress_body reverses the codings listed in a Content-Encoding header value, codings are
+/// undone in the reverse order of their application
+fn decompress_body(bytes: &[u8], content_encoding: Option<&str>) -> Result<Vec<u8>, Error> {
+    let mut body = bytes.to_vec();
+    let codings = match content_encoding {
+        Some(c) => c.split(',').map(|c| c.trim().to_ascii_lowercase()),
+        None => return Ok(body),
+    };
+    for coding in codings.rev() {
+        let mut decoded = Vec::new();
+        match coding.as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => {
+                flate2::read::GzDecoder::new(body.as_slice()).read_to_end(&mut decoded)
+            }
+            // "deflate" is meant to be zlib wrapped but raw deflate streams are common enough
+            "deflate" => flate2::read::ZlibDecoder::new(body.as_slice())
+                .read_to_end(&mut decoded)
+                .or_else(|_| {
+                    decoded.clear();
+                    flate2::read::DeflateDecoder::new(body.as_slice()).read_to_end(&mut decoded)
+                }),
+            "br" => brotli::Decompressor::new(body.as_slice(), 4096).read_to_end(&mut decoded),
+            c => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported Content-Encoding: {}", c),
+            )),
+        }
+        .context(format!("http::request {} decompression failure", coding))?;
+        body = decoded;
+    }
+    Ok(body)
+}
+
+/// decode_body represents the payload bytes as a Value, using the response format requested by the
+/// frame or otherwise inferring one from the returned Content-Type
+fn decode_body(
+    bytes: &[u8],
+    content_type: Option<&str>,
+    format: Option<ResponseFormat>,
+) -> Result<Option<Value>, Error> {
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+    let body = match format.unwrap_or_else(|| infer_format(content_type, bytes)) {
+        ResponseFormat::Json => {
+            serde_json::from_slice(bytes).context("http::request response.json() decode failure")?
+        }
+        ResponseFormat::Text => Value::String(String::from_utf8_lossy(bytes).into_owned()),
+        ResponseFormat::Base64 => Value::String(base64::encode(bytes)),
+        ResponseFormat::Sha256 => Value::String(format!("{:x}", Sha256::digest(bytes))),
+        ResponseFormat::Size => Value::from(bytes.len()),
+    };
+    Ok(Some(body))
+}
+
+/// infer_format picks a ResponseFormat from the media type of a Content-Type header value,
+/// falling back to inspecting the payload itself when the media type is unknown
+fn infer_format(content_type: Option<&str>, bytes: &[u8]) -> ResponseFormat {
+    let media_type = content_type
+        .and_then(|c| c.split(';').next())
+        .map(|m| m.trim().to_ascii_lowercase());
+
+    match media_type.as_deref() {
+        Some(m) if m == "application/json" || m.ends_with("+json") => ResponseFormat::Json,
+        Some(m) if m.starts_with("text/") || m.ends_with("xml") => ResponseFormat::Text,
+        Some(m)
+            if m.starts_with("image/")
+                || m.starts_with("audio/")
+                || m.starts_with("video/")
+                || m == "application/octet-stream" =>
+        {
+            ResponseFormat::Base64
+        }
+        None if serde_json::from_slice::<Value>(bytes).is_ok() => ResponseFormat::Json,
+        _ if std::str::from_utf8(bytes).is_ok() => ResponseFormat::Text,
+        _ => ResponseFormat::Base64,
+    }
+}
+
+/// header_to_value converts a response header map into a JSON object keyed by header name,
+/// header names repeated in the response such as `Set-Cookie` are collected into an array
+pub fn header_to_value(header: &HeaderMap) -> Value {
+    let mut map = Map::new();
+    for name in header.keys() {
+        let mut values: Vec<Value> = header
+            .get_all(name)
+            .iter()
+            .map(|v| Value::String(String::from_utf8_lossy(v.as_bytes()).into_owned()))
+            .collect();
+        let value = match values.len() {
+            1 => values.remove(0),
+            _ => Value::Array(values),
+        };
+        map.insert(name.to_string(), value);
+    }
+    Value::Object(map)
+}
+
+/// send executes a request and follows any redirects allowed by Params.redirect,
+/// returning the final response along with the status and url of every redirect taken
+pub fn send(
+    prm: &Params,
+    mut request: blocking::Request,
+) -> Result<(blocking::Response, Vec<Value>), Error> {
+    let client = prm.session.client(&prm.tls_config, prm.proxy.as_deref())?;
+    let max_hops = prm.redirect.map_or(Redirect::DEFAULT_MAX, |r| r.max_hops());
+    let mut chain = Vec::new();
+
+    loop {
+        let method = request.method().clone();
+        let mut headers = request.headers().clone();
+        let timeout = request.timeout().copied();
+        // requests with a body are only replayed for 307 and 308 redirects
+        let replay = request.try_clone();
+        let response = client.execute(request)?;
+
+        let status = response.status();
+        let location = match response.headers().get(header::LOCATION) {
+            Some(l) if is_redirect(status) && chain.len() < max_hops => {
+                response.url().join(l.to_str()?)?
+            }
+            _ => return Ok((response, chain)),
+        };
+        chain.push(json!({
+            "status": status.as_u16(),
+            "url": response.url().as_str(),
+            "location": location.as_str(),
+        }));
+
+        let cross_host = location.host_str() != response.url().host_str()
+            || location.port_or_known_default() != response.url().port_or_known_default();
+        if cross_host {
+            for h in &[
+                header::AUTHORIZATION,
+                header::COOKIE,
+                header::PROXY_AUTHORIZATION,
+                header::WWW_AUTHENTICATE,
+            ] {
+                headers.remove(h);
+            }
+        }
+
+        request = match status {
+            StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => replay
+                .ok_or_else(|| anyhow!("unable to replay the request body for a {}", status))?,
+            _ => {
+                for h in &[
+                    header::CONTENT_TYPE,
+                    header::CONTENT_LENGTH,
+                    header::CONTENT_ENCODING,
+                ] {
+                    headers.remove(h);
+                }
+                let method = match method {
+                    Method::HEAD => Method::HEAD,
+                    _ => Method::GET,
+                };
+                blocking::Request::new(method, location.clone())
+            }
+        };
+        *request.url_mut() = location;
+        *request.headers_mut() = headers;
+        *request.timeout_mut() = timeout;
+    }
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
 // request is used by run_request to send an http request and deserialize the returned data
 // into a Response struct
 pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
-    let response = build_request(&prm, req)?.send()?;
+    let request = build_request(&prm, req)?;
+    prm.stopwatch.start();
+    let (response, redirects) = send(&prm, request)?;
+    to_response(&prm, response, redirects)
+}
+
+/// to_response reads a returned HTTP response to completion, building the frame Response from its
+/// status, header and decoded body along with the session cookies and any redirects followed
+pub fn to_response<'a>(
+    prm: &Params,
+    response: blocking::Response,
+    redirects: Vec<Value>,
+) -> Result<Response<'a>, Error> {
+    info!("{} {:?}", "HTTP Version:".yellow(), response.version());
     let status = response.status().as_u16() as u32;
-    // reqwest.Response is a private Option<Value> field so we rely on
-    // the Response.content_length() method to get the exact body byte size
-    let response_body: Option<Value> = match response.content_length() {
-        Some(0) => None,
-        None => {
-            warn!("unable to determine Response body content length");
-            None
-        }
-        Some(_) => response
-            .json()
-            .context("http::request response.json() decode failure")?,
-    };
+    let response_header = header_to_value(response.headers());
+    let cookie = prm.session.cookies_for(response.url());
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let content_encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    // read the body to completion rather than relying on Response.content_length() since
+    // chunked and compressed payloads do not declare a length
+    let response_bytes = decompress_body(&response.bytes()?, content_encoding.as_deref())?;
+    let response_body = decode_body(
+        &response_bytes,
+        content_type.as_deref(),
+        prm.response_format,
+    )?;
+
+    let mut etc = json!({});
+    if !cookie.is_empty() {
+        etc["cookie"] = Value::Object(cookie);
+    }
+    if !redirects.is_empty() {
+        etc["redirects"] = Value::Array(redirects);
+    }
 
     Ok(Response {
-        // TODO add response headers
         body: response_body,
-        etc: Some(json!({})),
+        header: Some(response_header),
+        errors: None,
+        etc: Some(etc),
         validation: None,
+        max_ms: None,
         status,
     })
 }
@@ -108,7 +550,6 @@ pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use http::header;
     use rstest::*;
 
     fn case_build_header(case: u32) -> HeaderMap {
@@ -138,4 +579,327 @@ mod tests {
     fn test_build_header(string_header: &str, expected: HeaderMap) {
         assert_eq!(expected, build_header(string_header).unwrap());
     }
+
+    #[rstest(
+        content_type,
+        bytes,
+        format,
+        expected,
+        case(Some("application/json"), br#"{"a":1}"#, None, Some(json!({"a": 1}))),
+        case(Some("application/problem+json; charset=utf-8"), b"[]", None, Some(json!([]))),
+        case(Some("text/plain"), b"OK", None, Some(json!("OK"))),
+        case(Some("text/html"), b"{}", None, Some(json!("{}"))),
+        case(Some("application/octet-stream"), b"\x00\x01", None, Some(json!("AAE="))),
+        case(None, br#"{"a":1}"#, None, Some(json!({"a": 1}))),
+        case(None, b"healthy", None, Some(json!("healthy"))),
+        case(None, b"\xff\xfe", None, Some(json!("//4="))),
+        case(Some("text/csv"), b"a,b", Some(ResponseFormat::Size), Some(json!(3))),
+        case(
+            Some("text/plain"),
+            b"abc",
+            Some(ResponseFormat::Sha256),
+            Some(json!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"))
+        ),
+        case(Some("text/plain"), b"", None, None)
+    )]
+    fn test_decode_body(
+        content_type: Option<&str>,
+        bytes: &[u8],
+        format: Option<ResponseFormat>,
+        expected: Option<Value>,
+    ) {
+        assert_eq!(expected, decode_body(bytes, content_type, format).unwrap());
+    }
+
+    fn case_compress(encoding: &str, input: &[u8]) -> Vec<u8> {
+        use flate2::{read, Compression};
+
+        let mut output = Vec::new();
+        match encoding {
+            "gzip" => read::GzEncoder::new(input, Compression::default())
+                .read_to_end(&mut output)
+                .unwrap(),
+            "deflate" => read::ZlibEncoder::new(input, Compression::default())
+                .read_to_end(&mut output)
+                .unwrap(),
+            "raw_deflate" => read::DeflateEncoder::new(input, Compression::default())
+                .read_to_end(&mut output)
+                .unwrap(),
+            "br" => brotli::CompressorReader::new(input, 4096, 5, 22)
+                .read_to_end(&mut output)
+                .unwrap(),
+            _ => return input.to_vec(),
+        };
+        output
+    }
+
+    #[rstest(
+        bytes,
+        content_encoding,
+        case(case_compress("gzip", b"zipped"), Some("gzip")),
+        case(case_compress("deflate", b"zipped"), Some("deflate")),
+        case(case_compress("raw_deflate", b"zipped"), Some("deflate")),
+        case(case_compress("br", b"zipped"), Some("br")),
+        case(case_compress("gzip", &case_compress("br", b"zipped")), Some("br, gzip")),
+        case(b"zipped".to_vec(), Some("identity")),
+        case(b"zipped".to_vec(), None)
+    )]
+    fn test_decompress_body(bytes: Vec<u8>, content_encoding: Option<&str>) {
+        assert_eq!(
+            b"zipped".to_vec(),
+            decompress_body(&bytes, content_encoding).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decompress_body_err() {
+        assert!(decompress_body(b"zipped", Some("compress")).is_err());
+    }
+
+    #[rstest(
+        body,
+        raw,
+        expected,
+        case(json!({"a": 1}), None, br#"{"a":1}"#.to_vec()),
+        case(json!("<a>1</a>"), None, br#""<a>1</a>""#.to_vec()),
+        case(json!("<a>1</a>"), Some(json!(false)), br#""<a>1</a>""#.to_vec()),
+        case(json!("<a>1</a>"), Some(json!(true)), b"<a>1</a>".to_vec()),
+        case(json!({"a": 1}), Some(json!(true)), br#"{"a":1}"#.to_vec()),
+        case(json!("AAE="), Some(json!("base64")), vec![0, 1])
+    )]
+    fn test_body_bytes(body: Value, raw: Option<Value>, expected: Vec<u8>) {
+        assert_eq!(expected, body_bytes(body, raw.as_ref()).unwrap());
+    }
+
+    #[rstest(
+        body,
+        raw,
+        case(json!({"a": 1}), json!("base64")),
+        case(json!("not base64!"), json!("base64")),
+        case(json!("<a>1</a>"), json!("text"))
+    )]
+    fn test_body_bytes_err(body: Value, raw: Value) {
+        assert!(body_bytes(body, Some(&raw)).is_err());
+    }
+
+    #[test]
+    fn test_build_multipart() {
+        let dir = std::env::temp_dir().join("darkroom_test_build_multipart");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("report.csv"), "a,b").unwrap();
+
+        let map = json!({
+            "description": "quarterly report",
+            "count": 2,
+            "document": {
+                "file": "report.csv",
+                "content_type": "text/csv"
+            }
+        });
+        let form = build_multipart(map.as_object().unwrap(), Some(&dir)).unwrap();
+        let boundary = form.boundary().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // capture the raw request sent over the wire
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let terminator = format!("--{}--\r\n", boundary);
+        let server = std::thread::spawn(move || {
+            use std::io::Write;
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&raw).ends_with(&terminator) {
+                let n = stream.read(&mut buf).unwrap();
+                raw.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(raw).unwrap()
+        });
+        Client::new()
+            .post(format!("http://{}/upload", addr))
+            .multipart(form)
+            .send()
+            .unwrap();
+        let body = server.join().unwrap();
+
+        for expected in [
+            "Content-Disposition: form-data; name=\"description\"\r\n\r\nquarterly report",
+            "Content-Disposition: form-data; name=\"count\"\r\n\r\n2",
+            "Content-Disposition: form-data; name=\"document\"; filename=\"report.csv\"\r\n\
+             Content-Type: text/csv\r\n\r\na,b",
+        ]
+        .iter()
+        {
+            assert!(body.contains(expected), "{} not in {}", expected, body);
+        }
+    }
+
+    #[test]
+    fn test_build_multipart_missing_file() {
+        let map = json!({"document": {"file": "missing.csv"}});
+        assert!(
+            build_multipart(map.as_object().unwrap(), Some(Path::new("/nonexistent"))).is_err()
+        );
+    }
+
+    #[test]
+    fn test_session_cookies() {
+        let session = Session::default();
+        let url = Url::parse("http://localhost:8000/login").unwrap();
+        let set_cookie = [
+            "session=abc; Path=/".parse().unwrap(),
+            "admin=true; Path=/admin".parse().unwrap(),
+        ];
+        reqwest::cookie::CookieStore::set_cookies(
+            session.cookies.as_ref(),
+            &mut set_cookie.iter(),
+            &url,
+        );
+
+        assert_eq!(
+            json!({"session": "abc"}),
+            Value::Object(session.cookies_for(&url))
+        );
+        assert_eq!(
+            json!({"admin": "true", "session": "abc"}),
+            Value::Object(
+                session.cookies_for(&Url::parse("http://localhost:8000/admin/users").unwrap())
+            )
+        );
+        assert_eq!(2, session.to_cookie_value().as_array().unwrap().len());
+        assert_eq!(session, session.clone());
+        assert_ne!(session, Session::default());
+    }
+
+    #[rstest(
+        redirect,
+        expected_status,
+        expected_chain,
+        case(None, 200, json!([
+            {"status": 302, "url": "/start", "location": "/middle"},
+            {"status": 307, "url": "/middle", "location": "/end"},
+        ])),
+        case(Some(Redirect::Max(1)), 307, json!([
+            {"status": 302, "url": "/start", "location": "/middle"},
+        ])),
+        case(Some(Redirect::Follow(false)), 302, json!([]))
+    )]
+    fn test_send_redirects(
+        redirect: Option<Redirect>,
+        expected_status: u16,
+        expected_chain: Value,
+    ) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            use std::io::Write;
+            let mut request_lines = Vec::new();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !String::from_utf8_lossy(&raw).contains("\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    raw.extend_from_slice(&buf[..n]);
+                }
+                let head = String::from_utf8(raw).unwrap();
+                let request_line = head.lines().next().unwrap().to_string();
+                let reply = match request_line.split(' ').nth(1).unwrap() {
+                    "/start" => "302 Found\r\nLocation: /middle",
+                    "/middle" => "307 Temporary Redirect\r\nLocation: /end",
+                    _ => "200 OK",
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                    reply
+                )
+                .unwrap();
+                request_lines.push(request_line);
+                if !reply.contains("Location") || request_lines.len() > 2 {
+                    return request_lines;
+                }
+            }
+            request_lines
+        });
+
+        let prm = Params {
+            redirect,
+            ..Params::default()
+        };
+        let request = prm
+            .session
+            .client(&prm.tls_config, None)
+            .unwrap()
+            .post(format!("{}/start", addr))
+            .build()
+            .unwrap();
+        let (response, chain) = send(&prm, request).unwrap();
+        // the server only stops once a non redirect response is sent
+        if expected_status != 200 {
+            let _ = Client::new().get(format!("{}/end", addr)).send();
+        }
+        let request_lines = server.join().unwrap();
+
+        assert_eq!(expected_status, response.status().as_u16());
+        let chain = serde_json::to_string(&chain).unwrap().replace(&addr, "");
+        assert_eq!(
+            expected_chain,
+            serde_json::from_str::<Value>(&chain).unwrap()
+        );
+        // a 302 is followed with a GET while a 307 replays the method of the redirected request
+        if expected_status == 200 {
+            assert_eq!(
+                vec![
+                    "POST /start HTTP/1.1",
+                    "GET /middle HTTP/1.1",
+                    "GET /end HTTP/1.1"
+                ],
+                request_lines
+            );
+        }
+    }
+
+    #[test]
+    fn test_session_client() {
+        let session = Session::new(true);
+        let shared = session.clone();
+        let insecure = TlsConfig {
+            insecure: Some(true),
+            ..TlsConfig::default()
+        };
+        session.client(&TlsConfig::default(), None).unwrap();
+        shared.client(&TlsConfig::default(), None).unwrap();
+        shared.client(&insecure, None).unwrap();
+        shared
+            .client(&insecure, Some("http://localhost:3128"))
+            .unwrap();
+        assert_eq!(3, session.clients.lock().unwrap().len());
+        assert_eq!(session, shared);
+        assert_ne!(session, Session::new(true));
+
+        let cert_only = TlsConfig {
+            cert: Some(PathBuf::from("client.pem")),
+            ..TlsConfig::default()
+        };
+        assert!(session.client(&cert_only, None).is_err());
+    }
+
+    #[test]
+    fn test_header_to_value() {
+        let mut header = HeaderMap::new();
+        header.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        header.append(header::SET_COOKIE, "a=1".parse().unwrap());
+        header.append(header::SET_COOKIE, "b=2".parse().unwrap());
+        assert_eq!(
+            json!({
+                "content-type": "application/json",
+                "set-cookie": ["a=1", "b=2"]
+            }),
+            header_to_value(&header)
+        );
+    }
 }
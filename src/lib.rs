@@ -1,4 +1,8 @@
-use crate::params::BaseParams;
+use crate::{
+    auth::OAuth2,
+    http::Session,
+    params::{BaseParams, TlsConfig},
+};
 use anyhow::{anyhow, Error};
 use argh::FromArgs;
 use colored_json::{prelude::*, Colour, Styler};
@@ -8,11 +12,19 @@ use std::{convert::TryFrom, fs, path::PathBuf};
 #[cfg(feature = "man")]
 use crate::man::Man;
 
+pub mod auth;
+pub mod exec;
+pub mod graphql;
 pub mod grpc;
 pub mod http;
 pub mod params;
+pub mod plugin;
 pub mod record;
+pub mod sql;
+pub mod sse;
 pub mod take;
+pub mod transport;
+pub mod websocket;
 
 #[cfg(feature = "man")]
 mod man;
@@ -70,14 +82,50 @@ pub struct Command {
     #[argh(option, arg_name = "file")]
     cut_out: Option<PathBuf>,
 
+    /// output of the final cookie jar kept across HTTP frames
+    #[argh(option, arg_name = "file")]
+    cookie_out: Option<PathBuf>,
+
     /// interactive frame sequence transitions
     #[argh(switch, short = 'i')]
     interactive: bool,
 
+    /// force HTTP/2 with prior knowledge for HTTP frames, HTTPS otherwise negotiates it with ALPN
+    #[argh(switch)]
+    http2: bool,
+
     /// enable TLS (automatically inferred for HTTP/S)
     #[argh(switch)]
     tls: bool,
 
+    /// PEM encoded root certificates used to verify the server
+    #[argh(option, arg_name = "file")]
+    cacert: Option<PathBuf>,
+
+    /// PEM encoded client certificate for mutual TLS, requires --key
+    #[argh(option, arg_name = "file")]
+    cert: Option<PathBuf>,
+
+    /// PEM encoded private key of the client certificate
+    #[argh(option, arg_name = "file")]
+    key: Option<PathBuf>,
+
+    /// skip verification of the server certificate
+    #[argh(switch)]
+    insecure: bool,
+
+    /// proxy url that requests are sent through
+    #[argh(option, arg_name = "url")]
+    proxy: Option<String>,
+
+    /// OAuth2 client credentials or refresh token grant used to authenticate every frame
+    #[argh(option, arg_name = "file")]
+    oauth: Option<PathBuf>,
+
+    /// send gRPC frames with the grpcurl binary instead of the native client
+    #[argh(switch)]
+    grpcurl: bool,
+
     /// the path to a directory from which proto sources can be imported, for use with --proto flags.
     #[argh(option, arg_name = "dir")]
     proto_dir: Vec<PathBuf>,
@@ -86,6 +134,10 @@ pub struct Command {
     #[argh(option, short = 'p', arg_name = "file")]
     proto: Vec<PathBuf>,
 
+    /// pass compiled FileDescriptorSet files used for payload forming
+    #[argh(option, arg_name = "file")]
+    protoset: Vec<PathBuf>,
+
     #[argh(subcommand)]
     pub nested: SubCommand,
 }
@@ -100,9 +152,26 @@ impl Command {
             address:     self.address.clone(),
             proto_path:  self.proto_dir.clone(),
             proto:       self.proto.clone(),
+            protoset:    self.protoset.clone(),
             cut_out:     self.cut_out.clone(),
             interactive: self.interactive,
             verbose:     self.verbose,
+            frame_dir:   None,
+            cookie_out:  self.cookie_out.clone(),
+            session:     Session::new(self.http2),
+            tls_config:  self.tls_config(),
+            proxy:       self.proxy.clone(),
+            oauth:       self.oauth.clone().map(OAuth2::new),
+            grpcurl:     self.grpcurl,
+        }
+    }
+
+    pub fn tls_config(&self) -> TlsConfig {
+        TlsConfig {
+            cacert:   self.cacert.clone(),
+            cert:     self.cert.clone(),
+            key:      self.key.clone(),
+            insecure: if self.insecure { Some(true) } else { None },
         }
     }
 
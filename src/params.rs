@@ -1,21 +1,38 @@
-use crate::Command;
+use crate::{
+    auth::{Auth, OAuth2},
+    http::Session,
+    transport::Stopwatch,
+    Command,
+};
 use anyhow::{anyhow, Error};
 use filmreel::frame::Request;
 use log::{error, warn};
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 /// Parameters needed for a uri method to be sent.
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct Params<'a> {
-    pub timeout:       u64,
-    pub use_timestamp: bool,
-    pub tls:           bool,
-    pub header:        Option<String>,
-    pub address:       String,
-    pub proto_path:    Option<&'a Vec<PathBuf>>,
-    pub proto:         Option<&'a Vec<PathBuf>>,
-    pub attempts:      Option<Attempts>,
+    pub timeout:         u64,
+    pub use_timestamp:   bool,
+    pub tls:             bool,
+    pub header:          Option<String>,
+    pub address:         String,
+    pub proto_path:      Vec<PathBuf>,
+    pub proto:           Vec<PathBuf>,
+    pub protoset:        Vec<PathBuf>,
+    pub attempts:        Option<Attempts>,
+    pub response_format: Option<ResponseFormat>,
+    pub redirect:        Option<Redirect>,
+    pub receive:         Option<Receive>,
+    pub tls_config:      TlsConfig,
+    pub proxy:           Option<String>,
+    pub auth:            Option<Auth>,
+    pub oauth:           Option<OAuth2>,
+    pub grpcurl:         bool,
+    pub frame_dir:       Option<&'a Path>,
+    pub session:         Session,
+    pub stopwatch:       Stopwatch,
 }
 
 impl<'a> Params<'a> {
@@ -56,9 +73,21 @@ pub struct BaseParams {
     pub address:     Option<String>,
     pub proto_path:  Vec<PathBuf>,
     pub proto:       Vec<PathBuf>,
+    /// compiled FileDescriptorSet files used in place of proto sources
+    pub protoset:    Vec<PathBuf>,
     pub cut_out:     Option<PathBuf>,
     pub interactive: bool,
     pub verbose:     bool,
+    /// directory of the frame currently being taken, used to resolve frame relative filepaths
+    pub frame_dir:   Option<PathBuf>,
+    pub cookie_out:  Option<PathBuf>,
+    pub session:     Session,
+    pub tls_config:  TlsConfig,
+    pub proxy:       Option<String>,
+    /// provider of the bearer token sent with every frame lacking an auth block
+    pub oauth:       Option<OAuth2>,
+    /// send gRPC frames using the grpcurl binary rather than the native client
+    pub grpcurl:     bool,
 }
 
 #[derive(Clone, Copy, Deserialize, Default, Debug, PartialEq)]
@@ -67,6 +96,104 @@ pub struct Attempts {
     pub ms:    u64,
 }
 
+/// ResponseFormat declares how a returned payload should be represented in the response body
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    /// deserialize the payload as JSON
+    Json,
+    /// lossy UTF-8 string of the payload
+    Text,
+    /// base64 encoded string of the payload bytes
+    Base64,
+    /// hex encoded SHA-256 checksum of the payload bytes
+    Sha256,
+    /// byte size of the payload
+    Size,
+}
+
+/// Receive declares when a frame stops collecting the messages streamed back by a service
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Receive {
+    /// stop once this many messages have been received
+    pub count:      Option<usize>,
+    /// stop once this many milliseconds have passed, falling back to the timeout of the run
+    pub timeout_ms: Option<u64>,
+}
+
+/// TlsConfig holds the certificates used to verify a service and to authenticate against it
+#[derive(Clone, Serialize, Deserialize, Default, Debug, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM encoded root certificates trusted alongside the system ones
+    pub cacert:   Option<PathBuf>,
+    /// PEM encoded client certificate, used for mutual TLS along with key
+    pub cert:     Option<PathBuf>,
+    /// PEM encoded PKCS#8 private key of the client certificate
+    pub key:      Option<PathBuf>,
+    /// skip verification of the server certificate
+    pub insecure: Option<bool>,
+}
+
+impl TlsConfig {
+    /// or fills every unset field with the field of a fallback TlsConfig
+    pub fn or(self, fallback: &Self) -> Self {
+        Self {
+            cacert:   self.cacert.or_else(|| fallback.cacert.clone()),
+            cert:     self.cert.or_else(|| fallback.cert.clone()),
+            key:      self.key.or_else(|| fallback.key.clone()),
+            insecure: self.insecure.or(fallback.insecure),
+        }
+    }
+
+    /// with_dir resolves every relative path against the given directory
+    pub fn with_dir(self, dir: Option<&Path>) -> Self {
+        let join = |p: Option<PathBuf>| match dir {
+            Some(d) => p.map(|p| d.join(p)),
+            None => p,
+        };
+        Self {
+            cacert:   join(self.cacert),
+            cert:     join(self.cert),
+            key:      join(self.key),
+            insecure: self.insecure,
+        }
+    }
+
+    pub fn is_insecure(&self) -> bool {
+        self.insecure.unwrap_or(false)
+    }
+
+    /// is_set returns true when any option implying a TLS connection is given
+    pub fn is_set(&self) -> bool {
+        self.cacert.is_some() || self.cert.is_some() || self.key.is_some() || self.is_insecure()
+    }
+}
+
+/// Redirect declares the redirect policy of a single HTTP request
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Redirect {
+    /// follow up to the default number of redirects when true, never follow when false
+    Follow(bool),
+    /// follow at most the given number of redirects
+    Max(usize),
+}
+
+impl Redirect {
+    /// the number of redirects reqwest follows by default
+    pub const DEFAULT_MAX: usize = 10;
+
+    pub fn max_hops(&self) -> usize {
+        match self {
+            Self::Follow(true) => Self::DEFAULT_MAX,
+            Self::Follow(false) => 0,
+            Self::Max(n) => *n,
+        }
+    }
+}
+
 impl From<&Command> for BaseParams {
     fn from(cmd: &Command) -> Self {
         Self {
@@ -77,9 +204,17 @@ impl From<&Command> for BaseParams {
             address:     cmd.address.clone(),
             proto_path:  cmd.proto.clone(),
             proto:       cmd.proto.clone(),
+            protoset:    cmd.protoset.clone(),
             cut_out:     cmd.cut_out.clone(),
             interactive: cmd.interactive,
             verbose:     cmd.verbose,
+            frame_dir:   None,
+            cookie_out:  cmd.cookie_out.clone(),
+            session:     Session::new(cmd.http2),
+            tls_config:  cmd.tls_config(),
+            proxy:       cmd.proxy.clone(),
+            oauth:       cmd.oauth.clone().map(OAuth2::new),
+            grpcurl:     cmd.grpcurl,
         }
     }
 }
@@ -94,12 +229,11 @@ impl BaseParams {
             Some(i) => Some(i.to_string()),
             None => self.header.clone(),
         };
+        // protocols running locally such as Exec do not need an address, those that do check
+        // for an empty one in run_request
         let address = match request.get_entrypoint() {
             Some(i) => i,
-            None => self
-                .address
-                .clone()
-                .ok_or_else(|| anyhow!("Params: missing address"))?,
+            None => self.address.clone().unwrap_or_default(),
         };
 
         let attempts: Option<Attempts> = request
@@ -109,16 +243,66 @@ impl BaseParams {
             .map(|v| serde_json::from_value(v.clone()))
             .transpose()?;
 
-        let proto_path = match self.proto_path.len() {
-            0 => None,
-            _ => Some(&self.proto_path),
-        };
+        let response_format: Option<ResponseFormat> = request
+            .get_etc()
+            .as_ref()
+            .and_then(|e| e.get("response_format"))
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()?;
+
+        let redirect: Option<Redirect> = request
+            .get_etc()
+            .as_ref()
+            .and_then(|e| e.get("redirect"))
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()?;
+
+        let receive: Option<Receive> = request
+            .get_etc()
+            .as_ref()
+            .and_then(|e| e.get("receive"))
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()?;
+
+        let tls_config: TlsConfig = request
+            .get_etc()
+            .as_ref()
+            .and_then(|e| e.get("tls"))
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()?
+            .map(|t: TlsConfig| t.with_dir(self.frame_dir.as_deref()).or(&self.tls_config))
+            .unwrap_or_else(|| self.tls_config.clone());
 
-        let proto = match self.proto.len() {
-            0 => None,
-            _ => Some(&self.proto),
+        let proxy = match request.get_etc().as_ref().and_then(|e| e.get("proxy")) {
+            Some(serde_json::Value::String(p)) => Some(p.clone()),
+            Some(serde_json::Value::Null) | None => self.proxy.clone(),
+            _ => return Err(anyhow!("request[\"proxy\"] must be a string")),
         };
 
+        let auth: Option<Auth> = request
+            .get_etc()
+            .as_ref()
+            .and_then(|e| e.get("auth"))
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()?;
+
+        // frame protos are resolved from the frame directory as well as the run's import paths
+        let frame_proto = self.etc_paths(&request, "proto")?;
+        let mut proto_path = self.proto_path.clone();
+        if !frame_proto.is_empty() {
+            if proto_path.is_empty() {
+                proto_path.push(PathBuf::from("."));
+            }
+            proto_path.extend(self.frame_dir.clone());
+        }
+        proto_path.extend(self.frame_paths(self.etc_paths(&request, "proto_dir")?));
+
+        let mut proto = self.proto.clone();
+        proto.extend(frame_proto);
+
+        let mut protoset = self.protoset.clone();
+        protoset.extend(self.frame_paths(self.etc_paths(&request, "protoset")?));
+
         Ok(Params {
             timeout: self.timeout,
             use_timestamp: self.timestamp,
@@ -127,9 +311,47 @@ impl BaseParams {
             address,
             proto_path,
             proto,
+            protoset,
             attempts,
+            response_format,
+            redirect,
+            receive,
+            tls_config,
+            proxy,
+            // an explicit auth block takes precedence over the run's OAuth2 provider
+            oauth: match auth {
+                Some(_) => None,
+                None => self.oauth.clone(),
+            },
+            auth,
+            grpcurl: self.grpcurl,
+            frame_dir: self.frame_dir.as_deref(),
+            session: self.session.clone(),
+            stopwatch: Stopwatch::default(),
         })
     }
+
+    /// etc_paths reads a filepath or an array of filepaths from the request's etc object
+    fn etc_paths(&self, request: &Request, key: &str) -> Result<Vec<PathBuf>, Error> {
+        match request.get_etc().as_ref().and_then(|e| e.get(key)) {
+            Some(serde_json::Value::String(p)) => Ok(vec![PathBuf::from(p)]),
+            Some(v @ serde_json::Value::Array(_)) => Ok(serde_json::from_value(v.clone())?),
+            Some(serde_json::Value::Null) | None => Ok(vec![]),
+            _ => Err(anyhow!(
+                "request[\"{}\"] must be a string or an array of strings",
+                key
+            )),
+        }
+    }
+
+    /// frame_paths joins relative filepaths with the directory of the frame
+    fn frame_paths(&self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        match &self.frame_dir {
+            Some(dir) => paths.into_iter().map(|p| dir.join(p)).collect(),
+            None => paths,
+        }
+    }
+
     pub fn fmt_timestamp(&self) -> String {
         if self.timestamp {
             return format!("[{}] ", chrono::Utc::now());
@@ -167,9 +389,19 @@ mod tests {
             header:      Some("initial_header".to_string()),
             proto_dir:   vec![],
             proto:       vec![],
+            protoset:    vec![],
             verbose:     false,
             cut_out:     None,
+            cookie_out:  None,
             interactive: false,
+            http2:       false,
+            cacert:      Some(PathBuf::from("ca.pem")),
+            cert:        None,
+            key:         None,
+            insecure:    false,
+            proxy:       Some("http://proxy.initial_addr.com".to_string()),
+            oauth:       Some(PathBuf::from("oauth.json")),
+            grpcurl:     false,
             nested:      SubCommand::Version(Version { version: true }),
         };
         let request: Request = serde_json::from_str::<Frame>(
@@ -181,6 +413,19 @@ mod tests {
     "header": "Authorization: Bearer BIG_BEAR",
     "entrypoint": "localhost:8000",
     "uri": "POST /it/notes",
+    "response_format": "base64",
+    "redirect": 3,
+    "receive": {
+      "count": 2,
+      "timeout_ms": 500
+    },
+    "auth": {
+      "bearer": "BIG_BEAR"
+    },
+    "tls": {
+      "cert": "client.pem",
+      "key": "client.key"
+    },
     "attempts": {
       "times": 2,
       "ms": 200
@@ -200,22 +445,96 @@ mod tests {
         let params: Params = base_params.init(request).unwrap();
         assert_eq!(
             Params {
-                timeout:       30,
-                use_timestamp: false,
-                tls:           false,
-                header:        Some("\"Authorization: Bearer BIG_BEAR\"".to_string()),
-                address:       "localhost:8000".to_string(),
-                proto_path:    None,
-                proto:         None,
-                attempts:      Some(Attempts {
+                timeout:         30,
+                use_timestamp:   false,
+                tls:             false,
+                header:          Some("\"Authorization: Bearer BIG_BEAR\"".to_string()),
+                address:         "localhost:8000".to_string(),
+                proto_path:      vec![],
+                proto:           vec![],
+                protoset:        vec![],
+                attempts:        Some(Attempts {
                     times: 2,
                     ms:    200,
                 }),
+                response_format: Some(ResponseFormat::Base64),
+                redirect:        Some(Redirect::Max(3)),
+                receive:         Some(Receive {
+                    count:      Some(2),
+                    timeout_ms: Some(500),
+                }),
+                tls_config:      TlsConfig {
+                    cacert:   Some(PathBuf::from("ca.pem")),
+                    cert:     Some(PathBuf::from("client.pem")),
+                    key:      Some(PathBuf::from("client.key")),
+                    insecure: None,
+                },
+                proxy:           Some("http://proxy.initial_addr.com".to_string()),
+                auth:            Some(Auth::Bearer("BIG_BEAR".to_string())),
+                oauth:           None,
+                grpcurl:         false,
+                frame_dir:       None,
+                session:         base_params.session.clone(),
+                stopwatch:       Stopwatch::default(),
             },
             params
         )
     }
 
+    #[test]
+    fn test_init_protos() {
+        let args: Command = argh::FromArgs::from_args(
+            &["dark"],
+            &[
+                "--proto",
+                "base.proto",
+                "--protoset",
+                "base.protoset",
+                "version",
+            ],
+        )
+        .unwrap();
+        let mut base_params = args.base_params();
+        base_params.frame_dir = Some(PathBuf::from("frames"));
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "uri": "darkroom.Users/Create",
+            "entrypoint": "localhost:8000",
+            "proto": "users.proto",
+            "proto_dir": ["protos"],
+            "protoset": ["users.protoset"]
+        }))
+        .unwrap();
+
+        let params = base_params.init(request).unwrap();
+        assert_eq!(
+            vec![
+                PathBuf::from("."),
+                PathBuf::from("frames"),
+                PathBuf::from("frames/protos")
+            ],
+            params.proto_path
+        );
+        assert_eq!(
+            vec![PathBuf::from("base.proto"), PathBuf::from("users.proto")],
+            params.proto
+        );
+        assert_eq!(
+            vec![
+                PathBuf::from("base.protoset"),
+                PathBuf::from("frames/users.protoset")
+            ],
+            params.protoset
+        );
+
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "uri": "darkroom.Users/Create",
+            "entrypoint": "localhost:8000",
+            "proto": 1
+        }))
+        .unwrap();
+        assert!(base_params.init(request).is_err());
+    }
+
     #[test]
     fn test_iter_path_args() {
         let path_vec = vec![
@@ -0,0 +1,166 @@
+use crate::{exec, params::Params, transport::Transport};
+use anyhow::{anyhow, Context, Error};
+use filmreel::{frame::Request, response::Response};
+use serde_json::{json, Value};
+use std::{
+    ffi::OsStr,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// the prefix of the executables providing a protocol, `dark-proto-kafka` sends the frames
+/// declaring `"protocol": "kafka"`
+pub const PREFIX: &str = "dark-proto-";
+
+/// Plugin sends frames through an external executable: the hydrated request and the Params object
+/// are written to its stdin as `{"request": {...}, "params": {...}}` and a Response is read back
+/// from its stdout as `{"body": ..., "status": 0}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plugin {
+    pub path: PathBuf,
+}
+
+impl Plugin {
+    /// find looks up the `dark-proto-<name>` executable of a protocol on PATH
+    pub fn find(name: &str) -> Option<Self> {
+        which::which(format!("{}{}", PREFIX, name))
+            .ok()
+            .map(|path| Self { path })
+    }
+
+    /// find_in looks up the `dark-proto-<name>` executable of a protocol in the given paths
+    pub fn find_in<P: AsRef<OsStr>>(name: &str, paths: P) -> Option<Self> {
+        let cwd = std::env::current_dir().ok()?;
+        which::which_in(format!("{}{}", PREFIX, name), Some(paths), cwd)
+            .ok()
+            .map(|path| Self { path })
+    }
+}
+
+impl Transport for Plugin {
+    fn request(&self, prm: Params, req: Request) -> Result<Response<'static>, Error> {
+        let name = self.path.display().to_string();
+        let stdin = serde_json::to_vec(&json!({
+            "request": req,
+            "params": params_to_value(&prm)?,
+        }))?;
+
+        let child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(format!("failed to execute {}", name))?;
+        // a plugin that hangs is killed once the timeout passes rather than blocking the reel
+        let output = exec::wait_with_timeout(child, stdin, prm.timeout, &name)?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} failed with {}: {}",
+                name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+        serde_json::from_slice(&output.stdout)
+            .context(format!("{} did not return a response object", name))
+    }
+}
+
+/// params_to_value exposes the Params options a plugin can act upon, credentials and sessions are
+/// kept within darkroom
+fn params_to_value(prm: &Params) -> Result<Value, Error> {
+    Ok(json!({
+        "address": prm.address,
+        "timeout": prm.timeout,
+        "tls": prm.tls,
+        "tls_config": serde_json::to_value(&prm.tls_config)?,
+        "header": prm.header,
+        "proxy": prm.proxy,
+        "proto_path": prm.proto_path,
+        "proto": prm.proto,
+        "protoset": prm.protoset,
+        "receive": serde_json::to_value(prm.receive)?,
+        "frame_dir": prm.frame_dir,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    #[test]
+    fn test_request() {
+        let dir = std::env::temp_dir().join("darkroom_test_plugin");
+        fs::create_dir_all(&dir).unwrap();
+        let stub = dir.join("dark-proto-stub");
+        // the stub echoes the input it was given as the response body
+        fs::write(
+            &stub,
+            "#!/bin/sh\nprintf '{\"body\": '\ncat\nprintf ', \"status\": 202}'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plugin = Plugin::find_in("stub", &dir).unwrap();
+        assert_eq!(stub, plugin.path);
+        assert_eq!(None, Plugin::find_in("missing", &dir));
+
+        let req: Request =
+            serde_json::from_value(json!({"uri": "orders", "body": {"id": 1}, "key": "k"}))
+                .unwrap();
+        let prm = Params {
+            address: "kafka:9092".to_string(),
+            timeout: 30,
+            ..Params::default()
+        };
+        let response = plugin.request(prm, req).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(202, response.status);
+        let body = response.body.unwrap();
+        assert_eq!(
+            json!({"uri": "orders", "body": {"id": 1}, "key": "k"}),
+            body["request"]
+        );
+        assert_eq!(json!("kafka:9092"), body["params"]["address"]);
+        assert_eq!(json!(30), body["params"]["timeout"]);
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let dir = std::env::temp_dir().join("darkroom_test_plugin_timeout");
+        fs::create_dir_all(&dir).unwrap();
+        let stub = dir.join("dark-proto-hang");
+        fs::write(&stub, "#!/bin/sh\nexec sleep 10\n").unwrap();
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let plugin = Plugin::find_in("hang", &dir).unwrap();
+        let req: Request = serde_json::from_value(json!({"uri": "orders"})).unwrap();
+        let prm = Params {
+            timeout: 1,
+            ..Params::default()
+        };
+        let start = std::time::Instant::now();
+        let err = plugin.request(prm, req).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            format!("{} did not exit within the 1s timeout", stub.display()),
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_request_err() {
+        let req: Request = serde_json::from_value(json!({"uri": "orders"})).unwrap();
+        for path in ["false", "true", "darkroom-missing-plugin"].iter() {
+            let plugin = Plugin {
+                path: PathBuf::from(path),
+            };
+            assert!(plugin.request(Params::default(), req.clone()).is_err());
+        }
+    }
+}
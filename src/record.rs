@@ -1,15 +1,15 @@
-use crate::{guess_json_obj, params::BaseParams, take::*, Record, VirtualRecord};
+use crate::{guess_json_obj, http::Session, params::BaseParams, take::*, Record, VirtualRecord};
 use anyhow::{anyhow, Context, Error};
 use colored::*;
 use filmreel as fr;
-use fr::{cut::Register, frame::Frame, reel::*, ToStringHidden};
+use fr::{cut::Register, frame::Frame, reel::*, ToStringHidden, ToStringPretty};
 use log::{debug, error, warn};
 use std::{
     convert::TryFrom,
     fs,
     ops::Range,
     path::{Path, PathBuf},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 pub struct RecordRunner {
@@ -94,10 +94,18 @@ pub fn cmd_vrecord(cmd: VirtualRecord, mut base_params: BaseParams) -> Result<()
 }
 
 /// run_record runs through a Reel sequence using the darkroom::Record or darkroom::VirtualRecord structs
-pub fn run_record(mut runner: RecordRunner, base_params: BaseParams) -> Result<(), Error> {
+pub fn run_record(mut runner: RecordRunner, mut base_params: BaseParams) -> Result<(), Error> {
     let start = Instant::now();
     let duration = runner.duration;
-    let get_duration = || {
+    // the request latency of every frame taken so far
+    let mut timings: Vec<(String, Duration)> = Vec::new();
+    let get_duration = |timings: &[(String, Duration)]| {
+        if !timings.is_empty() {
+            warn!("[Frame latencies]");
+        }
+        for (name, latency) in timings {
+            warn!("{:45} {}ms", name, latency.as_millis().to_string().yellow());
+        }
         if duration {
             warn!(
                 "[Total record duration: {:.3}sec]",
@@ -114,30 +122,39 @@ pub fn run_record(mut runner: RecordRunner, base_params: BaseParams) -> Result<(
             .map(|dir| take_output(&dir, &&meta_frame.path));
 
         let mut info_str = format!("{} {:?}", "File:".yellow(), meta_frame.get_filename());
+        let timing_name = meta_frame
+            .alt_name
+            .clone()
+            .unwrap_or_else(|| meta_frame.get_filename());
         if let Some(alt_name) = meta_frame.alt_name {
             info_str = format!("{:45} | {} {}", info_str, "Name:".yellow(), alt_name);
         }
         warn!("{}{}", base_params.fmt_timestamp(), info_str,);
         warn!("{}", "=======================".green());
 
+        base_params.frame_dir = meta_frame.path.parent().map(Path::to_path_buf);
         let frame = Frame::try_from(meta_frame.path)?;
         // Frame to be mutably borrowed
         let mut payload_frame = frame.clone();
 
-        if let Err(e) = run_take(
+        match run_take(
             &mut payload_frame,
             &mut runner.register,
             &base_params,
             output,
         ) {
-            get_duration();
-            write_cut(
-                &base_params.cut_out,
-                &runner.register,
-                &runner.reel_name,
-                true,
-            )?;
-            return Err(e);
+            Ok(latency) => timings.push((timing_name, latency)),
+            Err(e) => {
+                get_duration(&timings);
+                write_cut(
+                    &base_params.cut_out,
+                    &runner.register,
+                    &runner.reel_name,
+                    true,
+                )?;
+                write_cookies(&base_params.cookie_out, &base_params.session)?;
+                return Err(e);
+            }
         }
     }
     warn!(
@@ -147,7 +164,7 @@ pub fn run_record(mut runner: RecordRunner, base_params: BaseParams) -> Result<(
         "Success 🎉 ".yellow(),
         "==========\n".green()
     );
-    get_duration();
+    get_duration(&timings);
 
     write_cut(
         &base_params.cut_out,
@@ -156,6 +173,8 @@ pub fn run_record(mut runner: RecordRunner, base_params: BaseParams) -> Result<(
         false,
     )?;
 
+    write_cookies(&base_params.cookie_out, &base_params.session)?;
+
     Ok(())
 }
 
@@ -212,6 +231,16 @@ where
     Ok(())
 }
 
+/// write_cookies dumps the session cookie jar to the PathBuf provided.
+pub fn write_cookies(cookie_out: &Option<PathBuf>, session: &Session) -> Result<(), Error> {
+    if let Some(path) = cookie_out {
+        debug!("writing cookie output to PathBuf...");
+        fs::write(path, session.to_cookie_value().to_string_pretty()?)
+            .context("unable to write to --cookie-out")?;
+    }
+    Ok(())
+}
+
 /// take_output grabs a Record command's output directory and joins it with a MetaFrame's file stem
 pub fn take_output<P: AsRef<Path>>(dir: &P, file: &P) -> PathBuf {
     let frame_stem: &str = file
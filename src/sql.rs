@@ -0,0 +1,279 @@
+use crate::params::Params;
+use anyhow::{anyhow, Context, Error};
+use filmreel::{frame::Request, response::Response};
+use rusqlite::{
+    types::{Value as SqlValue, ValueRef},
+    Connection, OpenFlags, Statement,
+};
+use serde_json::{json, Map, Number, Value};
+use std::path::PathBuf;
+
+// request is used by run_request to run the statement of the hydrated uri against the SQLite
+// file named by the entrypoint, binding the request body as the statement parameters
+pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
+    // a database named by the frame entrypoint sits alongside the frame
+    let mut path = PathBuf::from(&prm.address);
+    if let (Some(dir), Some(_)) = (prm.frame_dir, req.get_entrypoint()) {
+        path = dir.join(path);
+    }
+    // the database is never created so that a misspelt path is reported as such
+    let conn = Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_URI,
+    )
+    .context(format!("SQLite database: {}", path.display()))?;
+
+    let parameters = req.to_val_payload()?;
+    prm.stopwatch.start();
+    match execute(&conn, &req.get_uri(), parameters) {
+        Ok((rows, changes)) => Ok(Response {
+            body: rows,
+            etc: Some(json!({})),
+            status: changes as u32,
+            ..Response::default()
+        }),
+        // statements failing in SQLite return its result code as the response status
+        Err(rusqlite::Error::SqliteFailure(err, message)) => {
+            let message = message.unwrap_or_else(|| err.to_string());
+            Ok(Response {
+                body: Some(Value::String(message.clone())),
+                etc: Some(json!({
+                    "error": {"code": err.extended_code, "message": message}
+                })),
+                status: (err.extended_code & 0xff) as u32,
+                ..Response::default()
+            })
+        }
+        Err(e) => Err(Error::from(e).context("SQL request failure")),
+    }
+}
+
+/// check rejects statements declaring cut variables before they are hydrated, interpolating a
+/// value into the statement would let it inject SQL so values are bound from the request body
+pub fn check(req: &Request) -> Result<(), Error> {
+    let uri = req.get_uri();
+    let declared = uri
+        .match_indices("${")
+        .find(|(i, _)| !uri[..*i].ends_with('\\'));
+    if let Some((i, _)) = declared {
+        let var = uri[i..].split_inclusive('}').next().unwrap_or_default();
+        return Err(anyhow!(
+            "SQL statements cannot declare cut variables, bind {} through the request body",
+            var
+        ));
+    }
+    Ok(())
+}
+
+/// execute binds the parameters to a single statement and runs it, returning the rows of a
+/// statement returning columns along with the number of rows changed by it
+fn execute(
+    conn: &Connection,
+    sql: &str,
+    parameters: Option<Value>,
+) -> Result<(Option<Value>, usize), rusqlite::Error> {
+    let mut stmt = conn.prepare(sql)?;
+    bind(&mut stmt, parameters)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let readonly = stmt.readonly();
+
+    let mut rows = Vec::new();
+    let mut query = stmt.raw_query();
+    while let Some(row) = query.next()? {
+        let mut map = Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            map.insert(column.clone(), to_json(row.get_ref(i)?));
+        }
+        rows.push(Value::Object(map));
+    }
+    let changes = if readonly { 0 } else { conn.changes() as usize };
+
+    if columns.is_empty() {
+        return Ok((None, changes));
+    }
+    Ok((Some(Value::Array(rows)), changes))
+}
+
+/// bind sets the statement parameters from the request body, an array binds positional `?`
+/// parameters while an object binds named `:name`, `@name` or `$name` parameters. Names without
+/// a prefix are bound as `:name`
+fn bind(stmt: &mut Statement, parameters: Option<Value>) -> Result<(), rusqlite::Error> {
+    match parameters {
+        Some(Value::Array(values)) => {
+            if values.len() != stmt.parameter_count() {
+                return Err(rusqlite::Error::InvalidParameterCount(
+                    values.len(),
+                    stmt.parameter_count(),
+                ));
+            }
+            for (i, value) in values.into_iter().enumerate() {
+                stmt.raw_bind_parameter(i + 1, to_sql(value))?;
+            }
+        }
+        Some(Value::Object(values)) => {
+            for (name, value) in values.into_iter() {
+                let name = match name.chars().next() {
+                    Some(':') | Some('@') | Some('$') => name,
+                    _ => format!(":{}", name),
+                };
+                let index = stmt
+                    .parameter_index(&name)?
+                    .ok_or(rusqlite::Error::InvalidParameterName(name))?;
+                stmt.raw_bind_parameter(index, to_sql(value))?;
+            }
+        }
+        Some(Value::Null) | None => (),
+        Some(value) => stmt.raw_bind_parameter(1, to_sql(value))?,
+    }
+    Ok(())
+}
+
+/// to_sql converts a JSON parameter into an SQLite value, booleans are stored as integers and
+/// arrays or objects as JSON text
+fn to_sql(value: Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => SqlValue::Text(s),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
+
+/// to_json converts a column value into JSON, blobs are base64 encoded
+fn to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Value::String(base64::encode(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn database(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("darkroom_test_sql");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = std::fs::remove_file(&path);
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT UNIQUE, score REAL, avatar BLOB);
+                 INSERT INTO users (name, score, avatar) VALUES ('ben', 1.5, x'0001');",
+            )
+            .unwrap();
+        path
+    }
+
+    fn take(path: &Path, req: Value) -> Response<'static> {
+        let prm = Params {
+            address: path.to_string_lossy().into_owned(),
+            ..Params::default()
+        };
+        request(prm, serde_json::from_value(req).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_request() {
+        let path = database("test_request.db");
+
+        let insert = take(
+            &path,
+            json!({
+                "uri": "INSERT INTO users (name, score) VALUES (:name, @score), ('max', NULL)",
+                "body": {"name": "amy", "@score": 2}
+            }),
+        );
+        assert_eq!((None, 2), (insert.body, insert.status));
+
+        let select = take(
+            &path,
+            json!({
+                "uri": "SELECT id, name, score, avatar FROM users WHERE name <> ? ORDER BY id",
+                "body": ["max"]
+            }),
+        );
+        assert_eq!(
+            Some(json!([
+                {"id": 1, "name": "ben", "score": 1.5, "avatar": "AAE="},
+                {"id": 2, "name": "amy", "score": 2.0, "avatar": null}
+            ])),
+            select.body
+        );
+        assert_eq!(0, select.status);
+
+        let delete = take(
+            &path,
+            json!({"uri": "DELETE FROM users WHERE name = ?1 RETURNING id", "body": "amy"}),
+        );
+        assert_eq!((Some(json!([{"id": 2}])), 1), (delete.body, delete.status));
+    }
+
+    #[test]
+    fn test_check() {
+        let check_uri = |uri: &str| {
+            let req: Request =
+                serde_json::from_value(json!({"uri": uri, "body": {"id": "${USER_ID}"}})).unwrap();
+            check(&req)
+        };
+        assert!(check_uri("SELECT * FROM users WHERE id = :id").is_ok());
+        assert!(check_uri("SELECT '\\${USER_ID}' AS literal").is_ok());
+        assert_eq!(
+            "SQL statements cannot declare cut variables, bind ${USER_ID} through the request body",
+            check_uri("SELECT * FROM users WHERE id = ${USER_ID}")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_request_sqlite_error() {
+        let path = database("test_request_sqlite_error.db");
+        let response = take(
+            &path,
+            json!({"uri": "INSERT INTO users (name) VALUES (?)", "body": ["ben"]}),
+        );
+        assert_eq!(19, response.status);
+        assert_eq!(
+            Some(json!({"error": {
+                "code": 2067,
+                "message": "UNIQUE constraint failed: users.name"
+            }})),
+            response.etc
+        );
+    }
+
+    #[test]
+    fn test_request_err() {
+        let path = database("test_request_err.db");
+        for (address, req) in [
+            (
+                path.to_string_lossy().into_owned(),
+                json!({"uri": "SELECT * FROM users WHERE id = ?", "body": [1, 2]}),
+            ),
+            (
+                path.to_string_lossy().into_owned(),
+                json!({"uri": "SELECT * FROM users WHERE id = :id", "body": {"user_id": 1}}),
+            ),
+            ("missing.db".to_string(), json!({"uri": "SELECT 1"})),
+        ]
+        .iter()
+        {
+            let prm = Params {
+                address: address.clone(),
+                ..Params::default()
+            };
+            let req = serde_json::from_value(req.clone()).unwrap();
+            assert!(request(prm, req).is_err());
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{
+    http::{build_request, header_to_value, send, to_response},
+    params::Params,
+};
+use anyhow::{anyhow, Error};
+use filmreel::{frame::Request, response::Response};
+use http::header;
+use reqwest::blocking;
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Read},
+    time::{Duration, Instant},
+};
+
+const EVENT_STREAM: &str = "text/event-stream";
+
+// request is used by run_request to subscribe to a `text/event-stream`, collecting the events
+// received as the response body until enough events arrive or the timeout elapses
+pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
+    let receive = prm.receive.unwrap_or_default();
+    let timeout = match receive.timeout_ms {
+        Some(ms) => Some(Duration::from_millis(ms)),
+        None if prm.timeout != 0 => Some(Duration::from_secs(prm.timeout)),
+        None => None,
+    };
+
+    let mut request = build_request(&prm, req)?;
+    let headers = request.headers_mut();
+    headers.insert(
+        header::ACCEPT,
+        header::HeaderValue::from_static(EVENT_STREAM),
+    );
+    // events are parsed as they arrive, so the stream is requested without compression
+    headers.insert(
+        header::ACCEPT_ENCODING,
+        header::HeaderValue::from_static("identity"),
+    );
+    // the timeout bounds the whole subscription rather than a single read
+    *request.timeout_mut() = timeout;
+    let deadline = timeout.map(|t| Instant::now() + t);
+    prm.stopwatch.start();
+    let (response, redirects) = send(&prm, request)?;
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    // services refusing a subscription usually reply with a regular HTTP response
+    if !matches!(content_type, Some(c) if c.trim_start().starts_with(EVENT_STREAM)) {
+        return to_response(&prm, response, redirects);
+    }
+
+    let status = response.status().as_u16() as u32;
+    let header = header_to_value(response.headers());
+    let content_encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let mut reader = BufReader::new(decoder(response, content_encoding.as_deref())?);
+    let mut parser = EventParser::default();
+    let mut events = Vec::new();
+    let mut line = String::new();
+    while receive.count != Some(events.len()) {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => events.extend(parser.line(&line)),
+            // the subscription is over once its timeout elapses
+            Err(_) if matches!(deadline, Some(d) if Instant::now() >= d) => break,
+            Err(e) => return Err(Error::from(e).context("event stream read failure")),
+        }
+    }
+
+    Ok(Response {
+        body: Some(Value::Array(events)),
+        header: Some(header),
+        etc: Some(json!({})),
+        status,
+        ..Response::default()
+    })
+}
+
+/// decoder undoes the Content-Encoding of an event stream that is compressed regardless of the
+/// `identity` encoding requested, such as by a proxy
+fn decoder(
+    stream: blocking::Response,
+    content_encoding: Option<&str>,
+) -> Result<Box<dyn Read>, Error> {
+    let coding = content_encoding.map(|c| c.trim().to_ascii_lowercase());
+    Ok(match coding.as_deref() {
+        None | Some("") | Some("identity") => Box::new(stream),
+        Some("gzip") | Some("x-gzip") => Box::new(flate2::read::GzDecoder::new(stream)),
+        Some("deflate") => Box::new(flate2::read::ZlibDecoder::new(stream)),
+        Some("br") => Box::new(brotli::Decompressor::new(stream, 4096)),
+        Some(c) => return Err(anyhow!("unsupported event stream Content-Encoding: {}", c)),
+    })
+}
+
+/// EventParser assembles the lines of an event stream into `{"event", "id", "data"}` objects
+/// following the [event stream interpretation](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation)
+#[derive(Debug, Default)]
+struct EventParser {
+    event: Option<String>,
+    data:  Option<String>,
+    /// the last event id persists across events until the service sets another one
+    id:    Option<String>,
+}
+
+impl EventParser {
+    /// line consumes a single line of the stream, returning an event once a blank line dispatches
+    /// it. Data that is valid JSON is parsed, otherwise it is kept as a string
+    fn line(&mut self, line: &str) -> Option<Value> {
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        if line.is_empty() {
+            let event = self.event.take();
+            let data = self.data.take()?;
+            return Some(json!({
+                "event": event.unwrap_or_else(|| "message".to_string()),
+                "id": self.id,
+                "data": serde_json::from_str(&data).unwrap_or(Value::String(data)),
+            }));
+        }
+        // lines starting with a colon are comments, often sent to keep the connection alive
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            _ => (),
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use rstest::*;
+    use std::{io::Write, net::TcpListener};
+
+    #[test]
+    fn test_event_parser() {
+        let stream = concat!(
+            ": keep alive\n",
+            "data: {\"name\": \"ben\"}\n",
+            "\n",
+            "event: update\r\n",
+            "id: 1\r\n",
+            "data: first line\r\n",
+            "data:second line\r\n",
+            "\r\n",
+            "retry: 1000\n",
+            "\n",
+            "data\n",
+            "\n",
+        );
+        let mut parser = EventParser::default();
+        let events: Vec<Value> = stream
+            .split_inclusive('\n')
+            .filter_map(|l| parser.line(l))
+            .collect();
+        assert_eq!(
+            vec![
+                json!({"event": "message", "id": null, "data": {"name": "ben"}}),
+                json!({"event": "update", "id": "1", "data": "first line\nsecond line"}),
+                json!({"event": "message", "id": "1", "data": ""}),
+            ],
+            events
+        );
+    }
+
+    /// serve replies to a single subscription with the given stream, holding an event stream open
+    /// until the client hangs up
+    fn serve(content_type: &'static str, stream: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = conn.read(&mut buf).unwrap();
+            write!(
+                conn,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+                content_type,
+                stream.len(),
+                stream
+            )
+            .unwrap();
+            if !content_type.starts_with(EVENT_STREAM) {
+                write!(conn, "0\r\n\r\n").unwrap();
+            }
+            let _ = conn.read(&mut buf);
+        });
+        address
+    }
+
+    /// serve_gzip replies to a single subscription with a gzip compressed stream when the client
+    /// accepts gzip or when always is set, as a compressing proxy would
+    fn serve_gzip(stream: &'static str, always: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let n = conn.read(&mut buf).unwrap();
+            let head = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+            let accepts_gzip = head
+                .lines()
+                .any(|l| l.starts_with("accept-encoding:") && l.contains("gzip"));
+            let (encoding, payload) = if always || accepts_gzip {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(stream.as_bytes()).unwrap();
+                ("Content-Encoding: gzip\r\n", encoder.finish().unwrap())
+            } else {
+                ("", stream.as_bytes().to_vec())
+            };
+            write!(
+                conn,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n{}Transfer-Encoding: chunked\r\n\r\n{:x}\r\n",
+                encoding,
+                payload.len(),
+            )
+            .unwrap();
+            conn.write_all(&payload).unwrap();
+            write!(conn, "\r\n").unwrap();
+            let _ = conn.read(&mut buf);
+        });
+        address
+    }
+
+    fn params(address: String, count: Option<usize>, timeout_ms: u64) -> Params<'static> {
+        Params {
+            address,
+            timeout: 5,
+            receive: Some(crate::params::Receive {
+                count,
+                timeout_ms: Some(timeout_ms),
+            }),
+            ..Params::default()
+        }
+    }
+
+    #[test]
+    fn test_request_count() {
+        let address = serve(
+            "text/event-stream",
+            "data: {\"n\": 1}\n\ndata: {\"n\": 2}\n\ndata: {\"n\": 3}\n\n",
+        );
+        let req: Request = serde_json::from_value(json!({"uri": "GET /events"})).unwrap();
+        let response = request(params(address, Some(2), 5000), req).unwrap();
+
+        assert_eq!(
+            Some(json!([
+                {"event": "message", "id": null, "data": {"n": 1}},
+                {"event": "message", "id": null, "data": {"n": 2}},
+            ])),
+            response.body
+        );
+        assert_eq!(200, response.status);
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let address = serve(
+            "text/event-stream; charset=utf-8",
+            "event: ping\ndata: 1\n\n",
+        );
+        let req: Request = serde_json::from_value(json!({"uri": "GET /events"})).unwrap();
+        let start = Instant::now();
+        let response = request(params(address, Some(2), 300), req).unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            Some(json!([{"event": "ping", "id": null, "data": 1}])),
+            response.body
+        );
+    }
+
+    #[rstest(always, case(false), case(true))]
+    fn test_request_gzip(always: bool) {
+        let address = serve_gzip("data: {\"n\": 1}\n\ndata: {\"n\": 2}\n\n", always);
+        let req: Request = serde_json::from_value(json!({"uri": "GET /events"})).unwrap();
+        let response = request(params(address, Some(2), 5000), req).unwrap();
+
+        assert_eq!(
+            Some(json!([
+                {"event": "message", "id": null, "data": {"n": 1}},
+                {"event": "message", "id": null, "data": {"n": 2}},
+            ])),
+            response.body
+        );
+    }
+
+    #[test]
+    fn test_request_not_event_stream() {
+        let address = serve("application/json", r#"{"error": "unsubscribed"}"#);
+        let req: Request = serde_json::from_value(json!({"uri": "GET /events"})).unwrap();
+        let response = request(params(address, None, 300), req).unwrap();
+
+        assert_eq!(Some(json!({"error": "unsubscribed"})), response.body);
+    }
+}
@@ -1,8 +1,7 @@
 use crate::{
-    grpc, http,
     params::{BaseParams, Params},
-    record::write_cut,
-    Take, ToStringPretty, ToTakeColouredJson, ToTakeHiddenColouredJson,
+    record::{write_cookies, write_cut},
+    transport, Take, ToStringPretty, ToTakeColouredJson, ToTakeHiddenColouredJson,
 };
 use anyhow::{anyhow, Context, Error};
 use colored::*;
@@ -20,17 +19,39 @@ use std::{
     convert::TryFrom,
     fs,
     io::{self, prelude::*},
-    path::PathBuf,
-    thread, time,
+    path::{Path, PathBuf},
+    thread,
+    time::{self, Duration, Instant},
 };
 
-// run_request decides which protocol to use for sending a hydrated Frame Request
-pub fn run_request<'a>(params: Params, frame: Frame) -> Result<Response<'a>, Error> {
-    let request_fn = match frame.protocol {
-        Protocol::HTTP => http::request,
-        Protocol::GRPC => grpc::request,
+// run_request decides which protocol to use for sending a hydrated Frame Request, returning the
+// Response along with the latency of the send measured by the transport
+pub fn run_request<'a>(params: Params, frame: Frame) -> Result<(Response<'a>, Duration), Error> {
+    // registered transports and local commands are left to require an address of their own
+    if params.address.is_empty() && !matches!(frame.protocol, Protocol::Exec | Protocol::Other(_)) {
+        return Err(anyhow!("Params: missing address"));
+    }
+    let transport = transport::resolve(&frame.protocol)?;
+    let send = |params: Params| {
+        let start = Instant::now();
+        let response = transport.request(params.clone(), frame.get_request())?;
+        let latency = params.stopwatch.take().unwrap_or_else(|| start.elapsed());
+        Ok::<_, Error>((response, latency))
     };
-    request_fn(params, frame.get_request())
+    let (response, latency) = send(params.clone())?;
+
+    // an OAuth2 token rejected by the service is acquired again and the request retried once
+    match (&params.oauth, transport.unauthenticated()) {
+        (Some(oauth), Some(status)) if response.status == status => {
+            warn!(
+                "{} returned, acquiring a new OAuth2 token",
+                status.to_string().yellow()
+            );
+            oauth.invalidate();
+            send(params)
+        }
+        _ => Ok((response, latency)),
+    }
 }
 
 // process_response grabs the expected Response from the given Frame and attempts to match the values
@@ -41,6 +62,7 @@ pub fn process_response<'a, 'b>(
     frame: &'a mut Frame<'b>,
     cut_register: &'a mut Register,
     mut payload_response: Response<'b>,
+    latency: Duration,
     output: Option<PathBuf>,
 ) -> Result<&'a Register, Error> {
     // ----------------------------------------------------------------------------
@@ -72,6 +94,12 @@ pub fn process_response<'a, 'b>(
         if let Some(response_body) = &mut frame.response.body {
             Frame::hydrate_val(&frame.cut, response_body, &cut_register, false)?;
         }
+        if let Some(response_header) = &mut frame.response.header {
+            Frame::hydrate_val(&frame.cut, response_header, cut_register, false)?;
+        }
+        if let Some(errors) = &mut frame.response.errors {
+            Frame::hydrate_val(&frame.cut, errors, cut_register, false)?;
+        }
         if let Some(etc) = &mut frame.response.etc {
             Frame::hydrate_val(&frame.cut, etc, &cut_register, false)?;
         }
@@ -95,6 +123,24 @@ pub fn process_response<'a, 'b>(
         return Err(anyhow!("request/response mismatch"));
     }
 
+    // the latency budget is checked once the response values are known to match
+    if let Some(max_ms) = frame.response.max_ms {
+        if latency > Duration::from_millis(max_ms) {
+            params.error_timestamp();
+            error!(
+                "{}{}{}",
+                "= ".red(),
+                format!("Too Slow 🐢 {}ms > {}ms ", latency.as_millis(), max_ms).yellow(),
+                "===".red()
+            );
+            return Err(anyhow!(
+                "response took {}ms, exceeding max_ms of {}ms",
+                latency.as_millis(),
+                max_ms
+            ));
+        }
+    }
+
     // remove lowercase values
     cut_register.flush_ignored();
 
@@ -124,14 +170,25 @@ pub fn process_response<'a, 'b>(
 ///     expected structure
 ///    - Value Mismatch: output during process_response when the returned JSON values do not
 ///    match
+///    - Too Slow: output during process_response when the latency exceeds the response max_ms
+/// 6. Returns the latency of the final request
 pub fn run_take<'a>(
     frame: &'a mut Frame<'a>,
     register: &'a mut Register,
     base_params: &'a BaseParams,
     output: Option<PathBuf>,
-) -> Result<(), Error> {
+) -> Result<Duration, Error> {
     let interactive = base_params.interactive;
     let verbose = base_params.verbose;
+    // body files are read in before hydration so that they can reference cut variables
+    frame.load_body_file(
+        base_params
+            .frame_dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new("")),
+    )?;
+    // the transport checks the request before cut variables are interpolated into it
+    transport::resolve(&frame.protocol)?.check(&frame.get_request())?;
     let mut unhydrated_frame: Option<Frame> = None;
     // hidden_frame is meant to sanitize ${_HIDDEN} variables
     let hidden_frame: Option<Frame> = if interactive || verbose {
@@ -198,9 +255,11 @@ pub fn run_take<'a>(
                 attempts.ms.to_string().yellow(),
                 "ms",
             );
-            if let Ok(response) = run_request(params.clone(), frame.clone()) {
-                if process_response(&params, frame, register, response, output.clone()).is_ok() {
-                    return Ok(());
+            if let Ok((response, latency)) = run_request(params.clone(), frame.clone()) {
+                if process_response(&params, frame, register, response, latency, output.clone())
+                    .is_ok()
+                {
+                    return Ok(latency);
                 }
             }
             thread::sleep(time::Duration::from_millis(attempts.ms));
@@ -213,15 +272,16 @@ pub fn run_take<'a>(
         );
     }
 
-    let response = run_request(params.clone(), frame.clone())?;
-    match process_response(&params, frame, register, response, output) {
-        Ok(_) => Ok(()),
+    let (response, latency) = run_request(params.clone(), frame.clone())?;
+    info!("{} {}ms", "Latency:".yellow(), latency.as_millis());
+    match process_response(&params, frame, register, response, latency, output) {
+        Ok(_) => Ok(latency),
         Err(e) => Err(e),
     }
 }
 
 /// cmd_take runs a single take using the darkroom::Take struct
-pub fn cmd_take(cmd: Take, base_params: BaseParams) -> Result<(), Error> {
+pub fn cmd_take(cmd: Take, mut base_params: BaseParams) -> Result<(), Error> {
     let metaframe = MetaFrame::try_from(&cmd.frame)?;
 
     // set up cut register
@@ -235,6 +295,7 @@ pub fn cmd_take(cmd: Take, base_params: BaseParams) -> Result<(), Error> {
         cut_register = Register::from(&cut_str)?;
     }
 
+    base_params.frame_dir = cmd.frame.parent().map(Path::to_path_buf);
     // Frame to be mutably borrowed
     let frame = Frame::try_from(cmd.frame).context(metaframe.get_filename())?;
     let mut payload_frame = frame.clone();
@@ -251,6 +312,7 @@ pub fn cmd_take(cmd: Take, base_params: BaseParams) -> Result<(), Error> {
             metaframe.reel_name,
             true,
         )?;
+        write_cookies(&base_params.cookie_out, &base_params.session)?;
         return Err(e);
     }
 
@@ -261,6 +323,8 @@ pub fn cmd_take(cmd: Take, base_params: BaseParams) -> Result<(), Error> {
         false,
     )?;
 
+    write_cookies(&base_params.cookie_out, &base_params.session)?;
+
     warn!(
         "{}{}{}",
         "= ".green(),
@@ -333,14 +397,109 @@ mod tests {
         .unwrap();
         let payload_response = Response {
             body:       Some(json!("created user: BIG_BEN")),
+            header:     None,
+            errors:     None,
             etc:        Some(json!({})),
             validation: None,
+            max_ms:     None,
             status:     200,
         };
+        let mut slow_frame = frame.clone();
+        slow_frame.response.max_ms = Some(10);
         let mut register = Register::default();
         let params = Params::default();
-        let processed_register =
-            process_response(&params, &mut frame, &mut register, payload_response, None).unwrap();
+        let latency = Duration::from_millis(20);
+        assert!(process_response(
+            &params,
+            &mut slow_frame,
+            &mut Register::default(),
+            payload_response.clone(),
+            latency,
+            None
+        )
+        .is_err());
+        let processed_register = process_response(
+            &params,
+            &mut frame,
+            &mut register,
+            payload_response,
+            latency,
+            None,
+        )
+        .unwrap();
         assert_eq!(*processed_register, register!({"USER_ID"=>"BIG_BEN"}));
     }
+
+    #[test]
+    fn test_process_response_errors() {
+        let mut frame: Frame = serde_json::from_str(
+            r#"
+{
+  "protocol": "GraphQL",
+  "cut": {
+    "to": {
+      "ERR_MSG": "'response'.'errors'.[0].'message'"
+    }
+  },
+  "request": {
+    "query": "{ user(id: 1) { name } }",
+    "uri": "/graphql"
+  },
+  "response": {
+    "body": {"user": null},
+    "errors": [{"message": "${ERR_MSG}", "path": ["user"]}],
+    "status": 200
+  }
+}
+    "#,
+        )
+        .unwrap();
+        let payload_response = Response {
+            body: Some(json!({"user": null})),
+            errors: Some(json!([{"message": "user 1 not found", "path": ["user"]}])),
+            etc: Some(json!({})),
+            status: 200,
+            ..Response::default()
+        };
+        let mut register = Register::default();
+        let processed_register = process_response(
+            &Params::default(),
+            &mut frame,
+            &mut register,
+            payload_response,
+            Duration::from_millis(0),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            *processed_register,
+            register!({"ERR_MSG"=>"user 1 not found"})
+        );
+    }
+
+    #[test]
+    fn test_run_request_latency() {
+        // the transport prepares for a while before starting the stopwatch and sending
+        crate::transport::register(
+            "test_latency",
+            |prm: Params, _: filmreel::frame::Request| {
+                thread::sleep(Duration::from_millis(200));
+                prm.stopwatch.start();
+                Ok(Response::default())
+            },
+        )
+        .unwrap();
+        let frame: Frame = serde_json::from_value(json!({
+            "protocol": "test_latency",
+            "request": {"uri": ""},
+            "response": {"status": 0}
+        }))
+        .unwrap();
+        let params = Params {
+            address: "bus://latency".to_string(),
+            ..Params::default()
+        };
+        let (_, latency) = run_request(params, frame).unwrap();
+        assert!(latency < Duration::from_millis(200));
+    }
 }
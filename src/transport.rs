@@ -0,0 +1,269 @@
+use crate::{exec, graphql, grpc, http, params::Params, plugin::Plugin, sql, sse, websocket};
+use anyhow::{anyhow, Error};
+use filmreel::{
+    frame::{Protocol, Request},
+    response::Response,
+};
+use lazy_static::lazy_static;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    /// transports registered for the protocols darkroom does not provide, keyed by protocol name
+    static ref TRANSPORTS: RwLock<HashMap<String, Arc<dyn Transport>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Transport sends a hydrated Frame Request using the options of the Params object, returning
+/// the Response to be compared against the one expected by the frame.
+///
+/// Closures taking a Params object and a Request implement Transport so that they can be
+/// registered as is:
+///
+/// ```
+/// use darkroom::{params::Params, transport};
+/// use filmreel::{frame::Request, response::Response};
+/// use serde_json::json;
+///
+/// transport::register("echo", |_prm: Params, req: Request| {
+///     Ok(Response {
+///         body: req.to_val_payload()?,
+///         etc: Some(json!({})),
+///         ..Response::default()
+///     })
+/// })
+/// .unwrap();
+/// ```
+pub trait Transport: Send + Sync {
+    fn request(&self, prm: Params, req: Request) -> Result<Response<'static>, Error>;
+
+    /// unauthenticated is the response status returned when an OAuth2 bearer token is
+    /// rejected, such a response acquires a new token and sends the request again
+    fn unauthenticated(&self) -> Option<u32> {
+        None
+    }
+
+    /// check is given the frame Request before it is hydrated so that a transport can reject cut
+    /// variables declared where their values cannot be sent safely
+    fn check(&self, _req: &Request) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<F> Transport for F
+where
+    F: Fn(Params, Request) -> Result<Response<'static>, Error> + Send + Sync,
+{
+    fn request(&self, prm: Params, req: Request) -> Result<Response<'static>, Error> {
+        self(prm, req)
+    }
+}
+
+/// Builtin pairs the request fn of a protocol provided by darkroom with its unauthenticated
+/// status and the check of its unhydrated requests
+struct Builtin(
+    fn(Params, Request) -> Result<Response<'static>, Error>,
+    Option<u32>,
+    fn(&Request) -> Result<(), Error>,
+);
+
+impl Transport for Builtin {
+    fn request(&self, prm: Params, req: Request) -> Result<Response<'static>, Error> {
+        (self.0)(prm, req)
+    }
+
+    fn unauthenticated(&self) -> Option<u32> {
+        self.1
+    }
+
+    fn check(&self, req: &Request) -> Result<(), Error> {
+        (self.2)(req)
+    }
+}
+
+/// unchecked accepts every request, cut variables can be declared anywhere in them
+fn unchecked(_: &Request) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Stopwatch times how long a transport spends sending a request and receiving its response so
+/// that the latency of a frame leaves out the work done before the send, such as acquiring an
+/// OAuth2 token or compiling gRPC descriptors. Transports that never start it are timed as a whole
+#[derive(Clone, Debug, Default)]
+pub struct Stopwatch(Arc<Mutex<Lap>>);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Lap {
+    start: Option<Instant>,
+    stop:  Option<Instant>,
+}
+
+impl Stopwatch {
+    /// start is called right before a request is sent, restarting any previous lap
+    pub fn start(&self) {
+        *self.0.lock().expect("stopwatch lock poisoned") = Lap {
+            start: Some(Instant::now()),
+            stop:  None,
+        };
+    }
+
+    /// stop is called once the response is received when more work follows before the transport
+    /// returns, otherwise the lap ends when the latency is taken
+    pub fn stop(&self) {
+        let mut lap = self.0.lock().expect("stopwatch lock poisoned");
+        if lap.start.is_some() {
+            lap.stop = Some(Instant::now());
+        }
+    }
+
+    /// take returns the duration of the lap started by the transport, resetting the stopwatch
+    pub fn take(&self) -> Option<Duration> {
+        let lap = std::mem::take(&mut *self.0.lock().expect("stopwatch lock poisoned"));
+        lap.start
+            .map(|start| lap.stop.unwrap_or_else(Instant::now).duration_since(start))
+    }
+}
+
+impl PartialEq for Stopwatch {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+            || *self.0.lock().expect("stopwatch lock poisoned")
+                == *other.0.lock().expect("stopwatch lock poisoned")
+    }
+}
+
+/// register makes a transport available to frames declaring `"protocol": name`, replacing any
+/// transport previously registered under that name. The names of the protocols darkroom provides
+/// cannot be registered
+pub fn register<T>(name: &str, transport: T) -> Result<(), Error>
+where
+    T: Transport + 'static,
+{
+    if !matches!(serde_json::from_value(name.into()), Ok(Protocol::Other(_))) {
+        return Err(anyhow!("{} is a darkroom protocol", name));
+    }
+    TRANSPORTS
+        .write()
+        .expect("transport registry poisoned")
+        .insert(name.to_string(), Arc::new(transport));
+    Ok(())
+}
+
+/// resolve returns the transport sending the requests of the given protocol, protocols darkroom
+/// does not provide are looked up in the registry and then on PATH as a
+/// [`Plugin`](crate::plugin::Plugin)
+pub fn resolve(protocol: &Protocol) -> Result<Arc<dyn Transport>, Error> {
+    let builtin = match protocol {
+        Protocol::HTTP => Builtin(http::request, Some(401), unchecked),
+        Protocol::GRPC => Builtin(grpc::request, Some(16), unchecked),
+        Protocol::GraphQL => Builtin(graphql::request, Some(401), unchecked),
+        Protocol::WebSocket => Builtin(websocket::request, Some(401), unchecked),
+        Protocol::SSE => Builtin(sse::request, Some(401), unchecked),
+        Protocol::Exec => Builtin(exec::request, None, unchecked),
+        Protocol::SQL => Builtin(sql::request, None, sql::check),
+        Protocol::Other(name) => {
+            if let Some(transport) = TRANSPORTS
+                .read()
+                .expect("transport registry poisoned")
+                .get(name)
+            {
+                return Ok(transport.clone());
+            }
+            // protocols that are not registered fall back to a `dark-proto-<name>` plugin
+            return match Plugin::find(name) {
+                Some(plugin) => Ok(Arc::new(plugin)),
+                None => Err(anyhow!(
+                    "no transport registered for protocol {} and `dark-proto-{}` was not found! \
+                     Check your PATH!",
+                    name,
+                    name
+                )),
+            };
+        }
+    };
+    Ok(Arc::new(builtin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Static answers every request with the same status
+    struct Static(u32);
+
+    impl Transport for Static {
+        fn request(&self, _: Params, _: Request) -> Result<Response<'static>, Error> {
+            Ok(Response {
+                status: self.0,
+                ..Response::default()
+            })
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        register("test_static", Static(7)).unwrap();
+        register("test_closure", |prm: Params, req: Request| {
+            Ok(Response {
+                body: Some(json!({"address": prm.address, "uri": req.get_uri()})),
+                ..Response::default()
+            })
+        })
+        .unwrap();
+
+        let transport = resolve(&Protocol::Other("test_static".to_string())).unwrap();
+        let req: Request = serde_json::from_value(json!({"uri": "ping"})).unwrap();
+        assert_eq!(7, transport.request(Params::default(), req).unwrap().status);
+        assert_eq!(None, transport.unauthenticated());
+
+        let transport = resolve(&Protocol::Other("test_closure".to_string())).unwrap();
+        let prm = Params {
+            address: "bus://orders".to_string(),
+            ..Params::default()
+        };
+        let req: Request = serde_json::from_value(json!({"uri": "orders.Create"})).unwrap();
+        assert_eq!(
+            Some(json!({"address": "bus://orders", "uri": "orders.Create"})),
+            transport.request(prm, req).unwrap().body
+        );
+    }
+
+    #[test]
+    fn test_register_err() {
+        assert!(register("gRPC", Static(0)).is_err());
+        assert!(register("HTTP", Static(0)).is_err());
+        assert!(resolve(&Protocol::Other("test_unregistered".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_stopwatch() {
+        let stopwatch = Stopwatch::default();
+        assert_eq!(None, stopwatch.take());
+
+        stopwatch.start();
+        std::thread::sleep(Duration::from_millis(20));
+        stopwatch.stop();
+        std::thread::sleep(Duration::from_millis(50));
+        let lap = stopwatch.take().unwrap();
+        assert!(lap >= Duration::from_millis(20) && lap < Duration::from_millis(50));
+        // every lap is only taken once
+        assert_eq!(None, stopwatch.take());
+    }
+
+    #[test]
+    fn test_resolve_builtin() {
+        assert_eq!(
+            Some(16),
+            resolve(&Protocol::GRPC).unwrap().unauthenticated()
+        );
+        assert_eq!(None, resolve(&Protocol::SQL).unwrap().unauthenticated());
+
+        let req: Request = serde_json::from_value(json!({"uri": "/users/${USER_ID}"})).unwrap();
+        assert!(resolve(&Protocol::HTTP).unwrap().check(&req).is_ok());
+        assert!(resolve(&Protocol::SQL).unwrap().check(&req).is_err());
+    }
+}
@@ -0,0 +1,608 @@
+use crate::{
+    http::{build_header, header_to_value},
+    params::{Params, TlsConfig},
+};
+use anyhow::{anyhow, Context, Error};
+use filmreel::{frame::Request, response::Response};
+use http::header;
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Write},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+use tungstenite::{
+    client::IntoClientRequest,
+    handshake::HandshakeError,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    stream::MaybeTlsStream,
+    Connector, Message, WebSocket,
+};
+use url::Url;
+
+/// Socket is a client WebSocket over a plain or TLS connection
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Connection is an open WebSocket along with the response header of its opening handshake
+#[derive(Debug)]
+pub struct Connection {
+    socket: Socket,
+    header: Value,
+}
+
+// request is used by run_request to send every message of the request body over a WebSocket
+// and to collect the messages received in return as the response body
+pub fn request<'a>(prm: Params, req: Request) -> Result<Response<'a>, Error> {
+    let url = endpoint(&prm.address, &req.get_uri())?;
+    let keep_open = match req.get_etc().as_ref().and_then(|e| e.get("keep_open")) {
+        Some(Value::Bool(k)) => *k,
+        Some(Value::Null) | None => false,
+        _ => return Err(anyhow!("request[\"keep_open\"] must be a boolean")),
+    };
+    let messages = match req.to_val_payload()? {
+        Some(Value::Array(messages)) => messages,
+        Some(message) => vec![message],
+        None => vec![],
+    };
+
+    let mut connection = match prm.session.take_socket(&url) {
+        Some(connection) => {
+            prm.stopwatch.start();
+            connection
+        }
+        None => match connect(&prm, &url)? {
+            Ok(connection) => connection,
+            // a rejected handshake is returned as is so that frames can expect a 401 or 403
+            Err(rejection) => return Ok(rejection),
+        },
+    };
+    for message in messages {
+        connection.socket.send(to_message(message))?;
+    }
+
+    let receive = prm.receive.unwrap_or_default();
+    let timeout = match receive.timeout_ms {
+        Some(ms) => Some(Duration::from_millis(ms)),
+        None if prm.timeout != 0 => Some(Duration::from_secs(prm.timeout)),
+        None => None,
+    };
+    let (received, close) = receive_messages(&mut connection.socket, receive.count, timeout)?;
+
+    let mut etc = json!({});
+    let header = connection.header.clone();
+    match close {
+        Some(close) => etc["close"] = close,
+        None if keep_open => prm.session.keep_socket(&url, connection),
+        None => {
+            // the connection is dropped whether or not the server acknowledges the close
+            let _ = connection.socket.close(None);
+            let _ = connection.socket.flush();
+        }
+    }
+
+    Ok(Response {
+        body: Some(Value::Array(received)),
+        header: Some(header),
+        etc: Some(etc),
+        status: 101,
+        ..Response::default()
+    })
+}
+
+/// endpoint joins the frame uri to the address, `http` and `https` addresses are mapped to the
+/// `ws` and `wss` schemes
+fn endpoint(address: &str, uri: &str) -> Result<Url, Error> {
+    let mut url = Url::parse(address).context(format!("base url: {}", address))?;
+    let scheme = match url.scheme() {
+        "http" => Some("ws"),
+        "https" => Some("wss"),
+        "ws" | "wss" => None,
+        s => {
+            return Err(anyhow!(
+                "base url: {}, unsupported WebSocket scheme {}",
+                address,
+                s
+            ))
+        }
+    };
+    if let Some(scheme) = scheme {
+        url.set_scheme(scheme)
+            .map_err(|_| anyhow!("base url: {}, unable to set the {} scheme", address, scheme))?;
+    }
+    if uri.is_empty() {
+        return Ok(url);
+    }
+    Ok(url.join(uri)?)
+}
+
+/// connect performs the opening handshake, a handshake refused with an HTTP response is returned
+/// as the Err variant
+fn connect<'a>(prm: &Params, url: &Url) -> Result<Result<Connection, Response<'a>>, Error> {
+    let mut request = url.as_str().into_client_request()?;
+    if let Some(h) = &prm.header {
+        request.headers_mut().extend(build_header(h)?);
+    }
+    if let Some(oauth) = &prm.oauth {
+        let client = prm.session.client(&prm.tls_config, prm.proxy.as_deref())?;
+        let bearer = format!("Bearer {}", oauth.bearer(&client)?);
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse()?);
+    }
+
+    // the latency of a new connection includes its handshake but not the OAuth2 token request
+    prm.stopwatch.start();
+    let stream = open_stream(prm, url)?;
+    let connector = Connector::NativeTls(tls_connector(&prm.tls_config)?);
+    match tungstenite::client_tls_with_config(request, stream, None, Some(connector)) {
+        Ok((socket, response)) => Ok(Ok(Connection {
+            socket,
+            header: header_to_value(response.headers()),
+        })),
+        Err(HandshakeError::Failure(tungstenite::Error::Http(response))) => Ok(Err(Response {
+            body: response
+                .body()
+                .as_ref()
+                .filter(|b| !b.is_empty())
+                .map(|b| text_to_value(String::from_utf8_lossy(b).into_owned())),
+            header: Some(header_to_value(response.headers())),
+            etc: Some(json!({})),
+            status: response.status().as_u16() as u32,
+            ..Response::default()
+        })),
+        Err(HandshakeError::Failure(e)) => {
+            Err(Error::from(e).context(format!("WebSocket handshake with {}", url)))
+        }
+        Err(HandshakeError::Interrupted(_)) => {
+            Err(anyhow!("WebSocket handshake with {} was interrupted", url))
+        }
+    }
+}
+
+/// open_stream connects to the host of the url, through an HTTP `CONNECT` tunnel when a proxy is
+/// given
+fn open_stream(prm: &Params, url: &Url) -> Result<TcpStream, Error> {
+    let timeout = match prm.timeout {
+        0 => None,
+        t => Some(Duration::from_secs(t)),
+    };
+    let proxy = match &prm.proxy {
+        Some(p) => {
+            let proxy = Url::parse(p).context(format!("proxy: {}", p))?;
+            if proxy.scheme() != "http" {
+                return Err(anyhow!(
+                    "WebSocket frames can only be sent through an http:// proxy, got {}",
+                    p
+                ));
+            }
+            Some(proxy)
+        }
+        None => None,
+    };
+    let mut stream = tcp_connect(proxy.as_ref().unwrap_or(url), timeout)?;
+    let proxy = match proxy {
+        Some(proxy) => proxy,
+        None => return Ok(stream),
+    };
+
+    let authority = format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or(80)
+    );
+    let mut head = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if !proxy.username().is_empty() {
+        let credentials = format!("{}:{}", proxy.username(), proxy.password().unwrap_or(""));
+        head.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            base64::encode(credentials)
+        ));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+
+    // the reply is read a byte at a time so that no tunnelled bytes are consumed
+    stream.set_read_timeout(timeout)?;
+    let mut reply = Vec::new();
+    let mut byte = [0u8];
+    while !reply.ends_with(b"\r\n\r\n") {
+        if reply.len() > 8192 {
+            return Err(anyhow!("proxy CONNECT reply is too long"));
+        }
+        stream
+            .read_exact(&mut byte)
+            .context(format!("proxy CONNECT to {}", authority))?;
+        reply.push(byte[0]);
+    }
+    stream.set_read_timeout(None)?;
+    let reply = String::from_utf8_lossy(&reply);
+    let status_line = reply.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(stream),
+        _ => Err(anyhow!(
+            "proxy refused to CONNECT to {}: {}",
+            authority,
+            status_line
+        )),
+    }
+}
+
+/// tcp_connect opens a connection to the host of the url, trying every address it resolves to
+fn tcp_connect(url: &Url, timeout: Option<Duration>) -> Result<TcpStream, Error> {
+    let addrs = url
+        .socket_addrs(|| match url.scheme() {
+            "wss" | "https" => Some(443),
+            _ => Some(80),
+        })
+        .context(format!("unable to resolve {}", url))?;
+    let mut last_err = None;
+    for addr in addrs {
+        let stream = match timeout {
+            Some(t) => TcpStream::connect_timeout(&addr, t),
+            None => TcpStream::connect(addr),
+        };
+        match stream {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    match last_err {
+        Some(e) => Err(Error::from(e).context(format!("unable to connect to {}", url))),
+        None => Err(anyhow!("{} did not resolve to any address", url)),
+    }
+}
+
+/// tls_connector builds the connector of `wss` urls from the TLS options of the frame
+fn tls_connector(tls_config: &TlsConfig) -> Result<native_tls::TlsConnector, Error> {
+    let mut builder = native_tls::TlsConnector::builder();
+    builder.danger_accept_invalid_certs(tls_config.is_insecure());
+    if let Some(cacert) = &tls_config.cacert {
+        let pem = fs::read_to_string(cacert).context(format!("cacert: {}", cacert.display()))?;
+        const END: &str = "-----END CERTIFICATE-----";
+        for cert in pem.split_inclusive(END).filter(|c| c.contains(END)) {
+            builder.add_root_certificate(native_tls::Certificate::from_pem(cert.as_bytes())?);
+        }
+    }
+    match (&tls_config.cert, &tls_config.key) {
+        (Some(cert), Some(key)) => {
+            let cert = fs::read(cert).context(format!("cert: {}", cert.display()))?;
+            let key = fs::read(key).context(format!("key: {}", key.display()))?;
+            builder.identity(native_tls::Identity::from_pkcs8(&cert, &key)?);
+        }
+        (None, None) => (),
+        _ => return Err(anyhow!("a client cert and key must be provided together")),
+    }
+    Ok(builder.build()?)
+}
+
+/// receive_messages reads messages until count messages are received, the timeout elapses or
+/// the server closes the connection. The close frame sent by the server is returned alongside
+/// the messages received
+fn receive_messages(
+    socket: &mut Socket,
+    count: Option<usize>,
+    timeout: Option<Duration>,
+) -> Result<(Vec<Value>, Option<Value>), Error> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let mut received = Vec::new();
+    while count != Some(received.len()) {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            set_read_timeout(socket, Some(remaining))?;
+        }
+        match socket.read() {
+            Ok(Message::Text(text)) => received.push(text_to_value(text)),
+            Ok(Message::Binary(bytes)) => received.push(Value::String(base64::encode(bytes))),
+            Ok(Message::Close(frame)) => {
+                let frame = frame.unwrap_or(CloseFrame {
+                    code:   CloseCode::Status,
+                    reason: "".into(),
+                });
+                return Ok((received, Some(close_to_value(frame))));
+            }
+            // pings are answered by tungstenite itself
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                break
+            }
+            Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => {
+                let frame = CloseFrame {
+                    code:   CloseCode::Abnormal,
+                    reason: "".into(),
+                };
+                return Ok((received, Some(close_to_value(frame))));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    set_read_timeout(socket, None)?;
+    Ok((received, None))
+}
+
+fn set_read_timeout(socket: &Socket, timeout: Option<Duration>) -> io::Result<()> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(s) => s.set_read_timeout(timeout),
+        MaybeTlsStream::NativeTls(s) => s.get_ref().set_read_timeout(timeout),
+        _ => Ok(()),
+    }
+}
+
+/// to_message sends strings verbatim as text messages and any other value as JSON text
+fn to_message(value: Value) -> Message {
+    match value {
+        Value::String(text) => Message::Text(text),
+        value => Message::Text(value.to_string()),
+    }
+}
+
+/// text_to_value parses a text message as JSON, falling back to the text itself
+fn text_to_value(text: String) -> Value {
+    serde_json::from_str(&text).unwrap_or(Value::String(text))
+}
+
+/// close_to_value exposes the close frame of the server as `"response"."close"`, a close frame
+/// without a status has the code 1005 while a connection dropped without one has the code 1006
+fn close_to_value(frame: CloseFrame) -> Value {
+    json!({"code": u16::from(frame.code), "reason": frame.reason})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::net::TcpListener;
+    use tungstenite::handshake::server::{
+        ErrorResponse, Request as ServerRequest, Response as ServerResponse,
+    };
+
+    /// serve accepts WebSocket connections replying to every text message with its uppercase,
+    /// "close" closes the connection. Each accepted connection is counted
+    #[allow(clippy::result_large_err)]
+    fn serve(connections: usize) -> (String, std::thread::JoinHandle<usize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("ws://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut accepted = 0;
+            for stream in listener.incoming().take(connections) {
+                accepted += 1;
+                let callback = |req: &ServerRequest, res: ServerResponse| {
+                    if req.uri().path() == "/private" {
+                        let mut err = ErrorResponse::new(Some("unauthorized".to_string()));
+                        *err.status_mut() = http::StatusCode::UNAUTHORIZED;
+                        return Err(err);
+                    }
+                    Ok(res)
+                };
+                let mut socket = match tungstenite::accept_hdr(stream.unwrap(), callback) {
+                    Ok(socket) => socket,
+                    Err(_) => continue,
+                };
+                while let Ok(message) = socket.read() {
+                    match message {
+                        Message::Text(t) if t == "close" => {
+                            let frame = CloseFrame {
+                                code:   CloseCode::Normal,
+                                reason: "done".into(),
+                            };
+                            socket.close(Some(frame)).unwrap();
+                        }
+                        Message::Text(t) => socket.send(Message::Text(t.to_uppercase())).unwrap(),
+                        Message::Binary(b) => socket.send(Message::Binary(b)).unwrap(),
+                        _ => (),
+                    }
+                }
+            }
+            accepted
+        });
+        (address, server)
+    }
+
+    fn params(address: String, count: Option<usize>) -> Params<'static> {
+        Params {
+            address,
+            timeout: 5,
+            receive: Some(crate::params::Receive {
+                count,
+                timeout_ms: None,
+            }),
+            ..Params::default()
+        }
+    }
+
+    #[test]
+    fn test_request() {
+        let (address, server) = serve(1);
+        let req: Request = serde_json::from_value(json!({
+            "body": ["hello", {"name": "ben"}, "close"],
+            "uri": "/chat"
+        }))
+        .unwrap();
+        let response = request(params(address, None), req).unwrap();
+
+        assert_eq!(Some(json!(["HELLO", {"NAME": "BEN"}])), response.body);
+        assert_eq!(
+            Some(json!({"close": {"code": 1000, "reason": "done"}})),
+            response.etc
+        );
+        assert_eq!(101, response.status);
+        assert_eq!(1, server.join().unwrap());
+    }
+
+    #[test]
+    fn test_request_keep_open() {
+        let (address, server) = serve(1);
+        let prm = params(address, Some(1));
+        for (message, keep_open, expected) in [
+            ("first", true, "FIRST"),
+            ("second", true, "SECOND"),
+            ("last", false, "LAST"),
+        ]
+        .iter()
+        {
+            let req: Request = serde_json::from_value(json!({
+                "body": [message],
+                "uri": "",
+                "keep_open": keep_open
+            }))
+            .unwrap();
+            let response = request(prm.clone(), req).unwrap();
+            assert_eq!(Some(json!([expected])), response.body);
+        }
+        // every frame was sent over the single connection accepted by the server
+        assert_eq!(1, server.join().unwrap());
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let (address, server) = serve(1);
+        let mut prm = params(address, Some(3));
+        prm.receive.as_mut().unwrap().timeout_ms = Some(200);
+        let req: Request = serde_json::from_value(json!({"body": "only one", "uri": ""})).unwrap();
+        let start = Instant::now();
+        let response = request(prm, req).unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(Some(json!(["ONLY ONE"])), response.body);
+        assert_eq!(Some(json!({})), response.etc);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_request_rejected() {
+        let (address, server) = serve(1);
+        let req: Request = serde_json::from_value(json!({"uri": "/private"})).unwrap();
+        let response = request(params(address, None), req).unwrap();
+
+        assert_eq!(401, response.status);
+        assert_eq!(Some(json!("unauthorized")), response.body);
+        server.join().unwrap();
+    }
+
+    /// proxy starts an HTTP proxy tunnelling a single CONNECT request, returning its url along
+    /// with a receiver of the request line it was sent
+    fn proxy() -> (String, std::sync::mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8];
+            while !head.ends_with(b"\r\n\r\n") {
+                client.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            let line = String::from_utf8(head)
+                .unwrap()
+                .lines()
+                .next()
+                .unwrap()
+                .to_string();
+            let mut server = TcpStream::connect(line.split_whitespace().nth(1).unwrap()).unwrap();
+            sender.send(line).unwrap();
+            client
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .unwrap();
+            let (mut client_read, mut server_write) =
+                (client.try_clone().unwrap(), server.try_clone().unwrap());
+            // each side is shut down once the other one closes so that the server sees the
+            // client hang up
+            std::thread::spawn(move || {
+                let _ = io::copy(&mut client_read, &mut server_write);
+                let _ = server_write.shutdown(std::net::Shutdown::Both);
+            });
+            let _ = io::copy(&mut server, &mut client);
+            let _ = client.shutdown(std::net::Shutdown::Both);
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn test_request_proxy() {
+        let (address, server) = serve(1);
+        let (proxy, tunnel) = proxy();
+        let mut prm = params(address.clone(), None);
+        prm.proxy = Some(proxy);
+        let req: Request =
+            serde_json::from_value(json!({"body": ["hello", "close"], "uri": ""})).unwrap();
+        let response = request(prm, req).unwrap();
+
+        assert_eq!(Some(json!(["HELLO"])), response.body);
+        assert_eq!(1, server.join().unwrap());
+        assert_eq!(
+            format!("CONNECT {} HTTP/1.1", address.trim_start_matches("ws://")),
+            tunnel.recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_request_proxy_err() {
+        let mut prm = params("ws://localhost:8080".to_string(), None);
+        prm.proxy = Some("socks5://localhost:1080".to_string());
+        let req: Request = serde_json::from_value(json!({"uri": ""})).unwrap();
+        assert!(request(prm, req).is_err());
+    }
+
+    #[test]
+    fn test_request_insecure() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let identity = native_tls::Identity::from_pkcs8(
+            cert.serialize_pem().unwrap().as_bytes(),
+            cert.serialize_private_key_pem().as_bytes(),
+        )
+        .unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // the server echoes a single message over TLS, the verified handshake is refused first
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match acceptor.accept(stream.unwrap()) {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut socket = tungstenite::accept(stream).unwrap();
+                if let Ok(Message::Text(t)) = socket.read() {
+                    socket.send(Message::Text(t.to_uppercase())).unwrap();
+                }
+                let _ = socket.close(None);
+                return;
+            }
+        });
+
+        let prm = |insecure| Params {
+            tls_config: TlsConfig {
+                insecure: Some(insecure),
+                ..Default::default()
+            },
+            ..params(format!("https://localhost:{}", port), Some(1))
+        };
+        let req: Request = serde_json::from_value(json!({"body": "hello", "uri": ""})).unwrap();
+        // the self signed certificate is only accepted when it is not verified
+        assert!(request(prm(false), req.clone()).is_err());
+        let response = request(prm(true), req).unwrap();
+        assert_eq!(Some(json!(["HELLO"])), response.body);
+        server.join().unwrap();
+    }
+
+    #[rstest(
+        address,
+        uri,
+        expected,
+        case("ws://localhost:8080", "", "ws://localhost:8080/"),
+        case("http://localhost:8080", "/chat", "ws://localhost:8080/chat"),
+        case("https://localhost:8080/api/", "chat", "wss://localhost:8080/api/chat")
+    )]
+    fn test_endpoint(address: &str, uri: &str, expected: &str) {
+        assert_eq!(expected, endpoint(address, uri).unwrap().as_str());
+    }
+}